@@ -37,11 +37,6 @@ fasta = groups.sequence(fi=ASSEMBLY, s=True).seqfn
 sequences = []
 for group, seq in zip(groups, SeqIO.parse(fasta, format='fasta')):
     seq = str(seq.seq).upper()
-
-    # Flip reverse complemented sequences for easier coordinates mapping later
-    if group.strand == "-":
-        seq = seq[::-1]
-
     sequences.append((group, seq))
 # ANCHOR_END: fetch-sequences
 
@@ -62,25 +57,28 @@ for site in BedTool(SITES):
 
 
 # Function to predict IRs and "optimize" them
-def job(interval: Interval, sequence: str) -> Tuple[Interval, str, List[repeto.InvertedRepeat]]:
+def job(interval: Interval, sequence: str) -> Tuple[Interval, str, List[repeto.GenomicRepeat]]:
     # Predict all IRs in the given sequence
     irs, alnscores = repeto.predict(sequence.encode("ASCII"), min_score=MIN_DSRNA_SIZE, min_matches_run=MIN_DSRNA_SIZE)
 
-    # Transform IR coordinates from sequence-based to genome-based
-    for ir in irs:
-        ir.shift(interval.start)
+    # Transform IR coordinates from sequence-based to genome-based.
+    # Sequences for the minus strand are reverse complemented, and their coordinates are mirrored automatically.
+    irs = [
+        repeto.GenomicRepeat.from_window(interval.chrom, interval.strand, interval.start, interval.end, ir)
+        for ir in irs
+    ]
 
     # Remove IRs that don't overlap binding sites on both "sides" (dsRNA stem arms)
     # Calculate optimization score as the alignment score + (number of bases overlapping binding sites) * 2
     filtered, index = [], INDEX[(interval.chrom, interval.strand)]
     for ir, score in zip(irs, alnscores):
         # Skip IRs with too large gap
-        maxgap = max(segment.right.start - segment.left.end for segment in ir.segments)
+        maxgap = max(segment.right.start - segment.left.end for segment in ir.repeat.segments)
         if maxgap >= MAX_ARMS_DISTANCE:
             continue
 
         replicated = False
-        for segment in ir.segments:
+        for segment in ir.repeat.segments:
             # Calculate the number of base pairs in runs with length >= MIN_DSRNA_SIZE
             # that overlap with binding sites for each stem arm
             arm_overlaps = []
@@ -103,8 +101,9 @@ def job(interval: Interval, sequence: str) -> Tuple[Interval, str, List[repeto.I
     # Find the optimal combination of inverted repeats (=data-supported dsRNAs)
     # Maximize the alignment score and the total length of binding sites explained by the IR * 2
     irs, scores = zip(*filtered)
-    irs, _ = repeto.optimize(irs, scores)
-    return interval, sequence, irs
+    optimized, _ = repeto.optimize([ir.repeat for ir in irs], scores)
+    optimized = {id(x) for x in optimized}
+    return interval, sequence, [ir for ir in irs if id(ir.repeat) in optimized]
 
 
 # Apply the function to all sequences in parallel
@@ -118,9 +117,9 @@ results = [x for x in results if len(x[2]) > 0]  # drop sequences without matche
 # This file can be also used to visualize results in a genome browser later
 saveto = "inverted-repeats.bed"
 with open(saveto, 'w') as stream:
-    for window, _, irs in results:
+    for _, _, irs in results:
         for ir in irs:
-            stream.write(f"{ir.to_bed12(contig=window.chrom, strand=window.strand)}\n")
+            stream.write(f"{ir.to_bed12()}\n")
 
 irs = BedTool(saveto).sort().saveas(saveto)
 
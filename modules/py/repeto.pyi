@@ -1,4 +1,4 @@
-from typing import Sequence, List, Tuple
+from typing import Sequence, List, Optional, Tuple, Union, Hashable, Dict
 
 
 class Range:
@@ -91,6 +91,16 @@ class InvertedRepeat:
         """
         pass
 
+    def validate(self, seq: bytes, wobble: bool = False) -> Validation:
+        """
+        Check that the inverted repeat is complementary on the given sequence. Repeat coordinates must be relative to
+        the sequence start (see `shift`).
+
+        :param seq: raw ASCII string, DNA or RNA sequence
+        :param wobble: whether G-U (G-T) pairs are allowed in addition to Watson-Crick pairs
+        """
+        pass
+
     def to_bed12(self, contig: str, *args,
                  name: str = ".", score: int = 0, strand: str = ".", color: str = "0,0,0") -> str:
         """
@@ -107,6 +117,55 @@ class InvertedRepeat:
     def __eq__(self, other) -> bool: ...
 
 
+class GenomicRepeat:
+    """
+    Inverted repeat located on the given contig and strand. Coordinates are always in the forward genome orientation.
+    """
+    contig: str
+    strand: str
+    repeat: InvertedRepeat
+
+    __hash__ = None
+
+    def __init__(self, contig: str, strand: str, repeat: InvertedRepeat):
+        """
+        Wrap an inverted repeat in genome coordinates. Strand must be one of '+', '-', '.'.
+        """
+        pass
+
+    @staticmethod
+    def from_window(contig: str, strand: str, start: int, end: int, repeat: InvertedRepeat) -> "GenomicRepeat":
+        """
+        Map an inverted repeat predicted in the genomic window [start, end) to the genome coordinates.
+
+        Sequences of minus strand windows are expected to be reverse complemented (e.g. `bedtools getfasta -s`),
+        and repeat coordinates are mirrored accordingly. The input repeat is not modified.
+        """
+        pass
+
+    def to_bed12(self, *, name: str = ".", score: int = 0, color: str = "0,0,0") -> str:
+        """
+        Convert genomic repeat to a BED12 record.
+        """
+        pass
+
+    def __eq__(self, other) -> bool: ...
+
+
+class Validation:
+    """
+    Result of checking an inverted repeat against the underlying sequence.
+    """
+    complementarity: List[float]
+    """Fraction of base pairs allowed by the pairing rules in each segment"""
+    noncanonical: int
+    """Number of allowed base pairs that are not Watson-Crick"""
+    mismatches: List[Tuple[int, int]]
+    """Positions (left, right) that can't form a base pair"""
+
+    def is_valid(self) -> bool: ...
+
+
 def predict(seq: bytes, min_score: int, min_matches_run: int) -> Tuple[List[InvertedRepeat], List[int]]:
     """
     Predict inverted repeats in the given nucleic acid sequence.
@@ -119,7 +178,10 @@ def predict(seq: bytes, min_score: int, min_matches_run: int) -> Tuple[List[Inve
     pass
 
 
-def optimize(ir: List[InvertedRepeat], scores: List[int]) -> Tuple[List[InvertedRepeat], int]:
+Score = Union[int, float, Tuple[int, int], Tuple[float, float], Tuple[int, int, int], Tuple[float, float, float]]
+
+
+def optimize(ir: List[InvertedRepeat], scores: Sequence[Score]) -> Tuple[List[InvertedRepeat], Score]:
     """
     Find score-maximal and coherent set of inverted nucleic acid repeats.
 
@@ -141,7 +203,101 @@ def optimize(ir: List[InvertedRepeat], scores: List[int]) -> Tuple[List[Inverted
     Such coherent matching also represents a formally valid (but very rough) RNA secondary structure.
 
     :param ir: list of InvertedRepeat objects
-    :param scores: numerical score for each InvertedRepeat
+    :param scores: integer or floating point score for each InvertedRepeat (lists or numpy arrays), NaN is not allowed.
+        Floating point scores within a small relative tolerance are considered tied. Tuples of 2 or 3 objectives
+        are optimized lexicographically, i.e. each next objective only breaks ties in the previous ones.
     :return: Tuple containing an optimal set of inverted repeats and the associated total score
     """
     pass
+
+
+def optimize_groups(
+        ir: List[InvertedRepeat], scores: Sequence[Score], groups: Sequence[Hashable], parallel: bool = False
+) -> Dict[Hashable, Tuple[List[InvertedRepeat], Score]]:
+    """
+    Optimize groups of inverted repeats (e.g. from different contigs, strands or windows) independently in a single call.
+    See `optimize` for details.
+
+    :param ir: list of InvertedRepeat objects
+    :param scores: score for each InvertedRepeat, same as in `optimize`
+    :param groups: hashable group key (e.g. contig name) for each InvertedRepeat
+    :param parallel: optimize groups in parallel, ignored unless the package is built with the `parallel` feature
+    :return: Dictionary mapping each group key to its optimal set of inverted repeats and the total score
+    """
+    pass
+
+
+class TreeNode:
+    """
+    Inverted repeat together with repeats nested in its gaps.
+    """
+    repeat: InvertedRepeat
+    gap: Optional[int]
+    """Gap of the parent repeat that contains this one, counted from left to right between parent's blocks.
+    None for top-level repeats."""
+    children: List[TreeNode]
+    """Nested repeats ordered by their start position"""
+
+
+def nest(ir: List[InvertedRepeat]) -> List[TreeNode]:
+    """
+    Arrange coherent inverted repeats, e.g. the optimize output, into a tree where children are nested in gaps
+    of their parents.
+
+    :param ir: list of coherent InvertedRepeat objects
+    :return: top-level nodes ordered by their start position
+    """
+    pass
+
+
+class BasePairMetrics:
+    """
+    Base pair level agreement between predicted and reference repeats.
+    """
+    matched_reference: int
+    matched_predicted: int
+    total_reference: int
+    total_predicted: int
+    sensitivity: float
+    ppv: float
+    f1: float
+    mcc: float
+
+
+class ArmMetrics:
+    """
+    Repeat level agreement: a repeat is matched if both its arms overlap arms of a single repeat from the other set.
+    """
+    matched_reference: int
+    matched_predicted: int
+    total_reference: int
+    total_predicted: int
+    sensitivity: float
+    ppv: float
+    f1: float
+
+
+class Comparison:
+    basepairs: BasePairMetrics
+    arms: ArmMetrics
+
+
+def compare(
+        predicted: List[InvertedRepeat], reference: List[InvertedRepeat], length: int, slippage: bool = False
+) -> Comparison:
+    """
+    Compare predicted inverted repeats against the reference (e.g. known structure).
+
+    Base pairs metrics include sensitivity, PPV, F1 and MCC. The MCC is computed over all length * (length - 1) / 2
+    pairs of sequence positions: true positives are pairs matched one-to-one (with slippage, each predicted pair is
+    counted once even if it supports several reference pairs), unmatched predicted pairs are false positives,
+    unmatched reference pairs are false negatives and the remaining pairs of positions are true negatives. Ratios with
+    zero denominators are reported as 0.
+
+    :param predicted: list of predicted (optimized) inverted repeats
+    :param reference: list of reference inverted repeats
+    :param length: length of the sequence, used to count true negatives
+    :param slippage: whether a base pair (i, j) should match any of (i ± 1, j) and (i, j ± 1)
+    :return: base pair and arm level metrics
+    """
+    pass
@@ -0,0 +1,98 @@
+use pyo3::prelude::*;
+
+use repeto::compare;
+
+#[pyclass(get_all, module = "repeto")]
+#[derive(Clone)]
+pub struct BasePairMetrics {
+    matched_reference: usize,
+    matched_predicted: usize,
+    total_reference: usize,
+    total_predicted: usize,
+    sensitivity: f64,
+    ppv: f64,
+    f1: f64,
+    mcc: f64,
+}
+
+#[pymethods]
+impl BasePairMetrics {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "BasePairMetrics {{ sensitivity: {:.3}, ppv: {:.3}, f1: {:.3}, mcc: {:.3} }}",
+            self.sensitivity, self.ppv, self.f1, self.mcc
+        )
+    }
+}
+
+impl From<compare::BasePairMetrics> for BasePairMetrics {
+    fn from(value: compare::BasePairMetrics) -> Self {
+        Self {
+            matched_reference: *value.matched_reference(),
+            matched_predicted: *value.matched_predicted(),
+            total_reference: *value.total_reference(),
+            total_predicted: *value.total_predicted(),
+            sensitivity: *value.sensitivity(),
+            ppv: *value.ppv(),
+            f1: *value.f1(),
+            mcc: *value.mcc(),
+        }
+    }
+}
+
+#[pyclass(get_all, module = "repeto")]
+#[derive(Clone)]
+pub struct ArmMetrics {
+    matched_reference: usize,
+    matched_predicted: usize,
+    total_reference: usize,
+    total_predicted: usize,
+    sensitivity: f64,
+    ppv: f64,
+    f1: f64,
+}
+
+#[pymethods]
+impl ArmMetrics {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "ArmMetrics {{ sensitivity: {:.3}, ppv: {:.3}, f1: {:.3} }}",
+            self.sensitivity, self.ppv, self.f1
+        )
+    }
+}
+
+impl From<compare::ArmMetrics> for ArmMetrics {
+    fn from(value: compare::ArmMetrics) -> Self {
+        Self {
+            matched_reference: *value.matched_reference(),
+            matched_predicted: *value.matched_predicted(),
+            total_reference: *value.total_reference(),
+            total_predicted: *value.total_predicted(),
+            sensitivity: *value.sensitivity(),
+            ppv: *value.ppv(),
+            f1: *value.f1(),
+        }
+    }
+}
+
+#[pyclass(get_all, module = "repeto")]
+#[derive(Clone)]
+pub struct Comparison {
+    basepairs: BasePairMetrics,
+    arms: ArmMetrics,
+}
+
+#[pymethods]
+impl Comparison {
+    pub fn __repr__(&self) -> String {
+        format!("Comparison {{ {}, {} }}", self.basepairs.__repr__(), self.arms.__repr__())
+    }
+}
+
+impl From<compare::Comparison> for Comparison {
+    fn from(value: compare::Comparison) -> Self {
+        let (basepairs, arms) = (value.basepairs().clone(), value.arms().clone());
+        Self { basepairs: basepairs.into(), arms: arms.into() }
+    }
+}
@@ -1,9 +1,15 @@
 use itertools::Itertools;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 
-use repeats::{InvertedRepeat, Range, RepeatSegment};
+use comparison::{ArmMetrics, BasePairMetrics, Comparison};
+use nesting::TreeNode;
+use repeats::{GenomicRepeat, InvertedRepeat, Range, RepeatSegment, Validation};
 use repeto;
 
+mod comparison;
+mod nesting;
 mod repeats;
 
 #[pyfunction]
@@ -19,15 +25,58 @@ pub fn predict(seq: &[u8], min_score: i64, min_matches_run: usize) -> PyResult<(
 }
 
 
+/// Integer or floating point scores, e.g. Python lists or numpy arrays.
+/// Tuples of 2 or 3 objectives per repeat are optimized lexicographically.
+#[derive(FromPyObject)]
+pub enum Scores {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Int2(Vec<(i64, i64)>),
+    Float2(Vec<(f64, f64)>),
+    Int3(Vec<(i64, i64, i64)>),
+    Float3(Vec<(f64, f64, f64)>),
+}
+
+// Run the expression with the concrete scores vector
+macro_rules! dispatch {
+    ($scores:expr, $x:ident => $body:expr) => {
+        match $scores {
+            Scores::Int($x) => $body,
+            Scores::Float($x) => $body,
+            Scores::Int2($x) => $body,
+            Scores::Float2($x) => $body,
+            Scores::Int3($x) => $body,
+            Scores::Float3($x) => $body,
+        }
+    };
+}
+
+impl Scores {
+    fn validate(&self) -> PyResult<()> {
+        use repeto::optimize::Score;
+
+        if dispatch!(self, x => x.iter().all(|x| x.is_valid())) {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err("Scores must not be NaN"))
+        }
+    }
+}
+
 #[pyfunction]
-pub fn optimize(ir: Vec<Py<InvertedRepeat>>, scores: Vec<i64>) -> PyResult<(Vec<Py<InvertedRepeat>>, i64)> {
+pub fn optimize(ir: Vec<Py<InvertedRepeat>>, scores: Scores) -> PyResult<(Vec<Py<InvertedRepeat>>, PyObject)> {
+    scores.validate()?;
+
     // Transform to an optimized Rust representation
     let rs_ir = Python::with_gil(|py| -> Vec<repeto::repeats::inv::Repeat<isize>> {
         ir.iter().map(|x| { x.borrow(py).to_rs(py) }).collect()
     });
 
     // Run the solution
-    let (solution, total_score) = repeto::optimize::run(&rs_ir, &scores);
+    let (solution, total_score) = dispatch!(&scores, scores => {
+        let (solution, score) = repeto::optimize::run(&rs_ir, scores);
+        (solution, Python::with_gil(|py| score.into_py(py)))
+    });
 
     // Shallow copy solution repeats
     let ir = Python::with_gil(|py| {
@@ -39,13 +88,102 @@ pub fn optimize(ir: Vec<Py<InvertedRepeat>>, scores: Vec<i64>) -> PyResult<(Vec<
 }
 
 
+fn optimize_grouped<Score>(
+    py: Python, ir: &[repeto::repeats::inv::Repeat<isize>], scores: &[Score], groups: &[usize], parallel: bool,
+) -> Vec<(usize, Vec<usize>, PyObject)>
+    where Score: repeto::optimize::Score + Send + Sync + IntoPy<PyObject>
+{
+    let result = py.allow_threads(|| match parallel {
+        #[cfg(feature = "parallel")]
+        true => repeto::optimize::run_grouped_parallel(ir, scores, groups),
+        // Without the feature groups are always optimized sequentially, the result is the same
+        _ => repeto::optimize::run_grouped(ir, scores, groups),
+    });
+    result.into_iter().map(|(key, solution, score)| (key, solution, score.into_py(py))).collect()
+}
+
+#[pyfunction]
+#[pyo3(signature = (ir, scores, groups, parallel = false))]
+pub fn optimize_groups(
+    ir: Vec<Py<InvertedRepeat>>, scores: Scores, groups: Vec<PyObject>, parallel: bool,
+) -> PyResult<Py<PyDict>> {
+    if groups.len() != ir.len() {
+        return Err(PyValueError::new_err("Each repeat must have a group"));
+    }
+    scores.validate()?;
+
+    Python::with_gil(|py| {
+        let rs_ir = ir.iter().map(|x| x.borrow(py).to_rs(py)).collect_vec();
+
+        // Map arbitrary hashable keys to dense ids
+        let ids = PyDict::new(py);
+        let mut keys = Vec::new();
+        let mut rs_groups = Vec::with_capacity(groups.len());
+        for key in &groups {
+            let id = match ids.get_item(key) {
+                Some(id) => id.extract()?,
+                None => {
+                    ids.set_item(key, keys.len())?;
+                    keys.push(key);
+                    keys.len() - 1
+                }
+            };
+            rs_groups.push(id);
+        }
+
+        let result = dispatch!(&scores, scores => optimize_grouped(py, &rs_ir, scores, &rs_groups, parallel));
+
+        let selected = PyDict::new(py);
+        for (key, solution, score) in result {
+            let solution = solution.into_iter().map(|x| ir[x].clone_ref(py)).collect_vec();
+            selected.set_item(keys[key], (solution, score))?;
+        }
+        Ok(selected.into())
+    })
+}
+
+
+#[pyfunction]
+pub fn nest(ir: Vec<Py<InvertedRepeat>>) -> PyResult<Vec<TreeNode>> {
+    Python::with_gil(|py| {
+        let rs_ir = ir.iter().map(|x| x.borrow(py).to_rs(py)).collect_vec();
+        repeto::optimize::nest(&rs_ir, &(0..rs_ir.len()).collect_vec()).iter()
+            .map(|x| TreeNode::from_rs(x, &ir, py))
+            .collect()
+    })
+}
+
+
+#[pyfunction]
+#[pyo3(signature = (predicted, reference, length, slippage = false))]
+pub fn compare(
+    predicted: Vec<Py<InvertedRepeat>>, reference: Vec<Py<InvertedRepeat>>, length: usize, slippage: bool,
+) -> PyResult<Comparison> {
+    let (predicted, reference) = Python::with_gil(|py| {
+        let convert = |ir: &[Py<InvertedRepeat>]| ir.iter().map(|x| x.borrow(py).to_rs(py)).collect_vec();
+        (convert(&predicted), convert(&reference))
+    });
+
+    Ok(repeto::compare::run(&predicted, &reference, length, slippage).into())
+}
+
+
 #[pymodule]
 #[pyo3(name = "repeto")]
 fn py(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Range>()?;
     m.add_class::<RepeatSegment>()?;
     m.add_class::<InvertedRepeat>()?;
+    m.add_class::<GenomicRepeat>()?;
+    m.add_class::<Validation>()?;
+    m.add_class::<BasePairMetrics>()?;
+    m.add_class::<ArmMetrics>()?;
+    m.add_class::<Comparison>()?;
+    m.add_class::<TreeNode>()?;
     m.add_function(wrap_pyfunction!(predict, m)?)?;
     m.add_function(wrap_pyfunction!(optimize, m)?)?;
+    m.add_function(wrap_pyfunction!(optimize_groups, m)?)?;
+    m.add_function(wrap_pyfunction!(nest, m)?)?;
+    m.add_function(wrap_pyfunction!(compare, m)?)?;
     Ok(())
 }
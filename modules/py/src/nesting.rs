@@ -0,0 +1,41 @@
+use pyo3::{PyTraverseError, PyVisit};
+use pyo3::prelude::*;
+
+use repeto::optimize;
+
+use crate::repeats::InvertedRepeat;
+
+#[pyclass(get_all, module = "repeto")]
+#[derive(Clone)]
+pub struct TreeNode {
+    repeat: Py<InvertedRepeat>,
+    gap: Option<usize>,
+    children: Vec<Py<TreeNode>>,
+}
+
+#[pymethods]
+impl TreeNode {
+    pub fn __repr__(&self, py: Python) -> String {
+        let brange = self.repeat.borrow(py).brange(py);
+        format!("TreeNode {{ {}, gap: {:?}, children: {} }}", brange.__repr__(), self.gap, self.children.len())
+    }
+
+    pub fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        visit.call(&self.repeat)?;
+        for x in &self.children {
+            visit.call(x)?;
+        }
+        Ok(())
+    }
+
+    pub fn __clear__(&mut self) {}
+}
+
+impl TreeNode {
+    pub fn from_rs(node: &optimize::TreeNode, ir: &[Py<InvertedRepeat>], py: Python) -> PyResult<Self> {
+        let children = node.children().iter()
+            .map(|x| Py::new(py, TreeNode::from_rs(x, ir, py)?))
+            .collect::<PyResult<_>>()?;
+        Ok(Self { repeat: ir[*node.rnaid()].clone_ref(py), gap: *node.gap(), children })
+    }
+}
@@ -3,11 +3,14 @@ use std::iter::zip;
 
 use itertools::{chain, Itertools};
 use pyo3::{PyTraverseError, PyVisit};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::pyclass::CompareOp;
 use pyo3::types::PyTuple;
 
 use repeto::repeats;
+use repeto::repeats::genomic::Strand;
+use repeto::repeats::pairing::PairingRules;
 
 #[pyclass(get_all, module = "repeto")]
 #[derive(Clone, PartialEq, Eq)]
@@ -185,6 +188,13 @@ impl InvertedRepeat {
         ).collect()
     }
 
+    #[pyo3(signature = (seq, wobble = false))]
+    pub fn validate(&self, py: Python, seq: &[u8], wobble: bool) -> Validation {
+        let rules = if wobble { PairingRules::Wobble } else { PairingRules::WatsonCrick };
+        let (complementarity, noncanonical, mismatches) = self.to_rs(py).validate(seq, rules).dissolve();
+        Validation { complementarity, noncanonical, mismatches }
+    }
+
     #[pyo3(
         signature = (contig, *args, name = ".", score = 0, strand = ".", color = "0,0,0"),
         text_signature = None
@@ -192,21 +202,14 @@ impl InvertedRepeat {
     pub fn to_bed12(&self, py: Python, contig: &str,
                     args: &PyTuple, name: &str, score: u16, strand: &str, color: &str) -> String {
         assert_eq!(args.len(), 0, "to_bed12 doesn't support positional arguments except 'contig'.");
-        assert!(score <= 1000, "Score must be from 0 to 1000");
-
-        let range = self.brange(py);
-        let (block_sizes, block_starts): (Vec<usize>, Vec<isize>) = self.seqranges(py)
+        let blocks = self.seqranges(py)
             .into_iter()
-            .map(|x| (x.borrow(py).__len__(), x.borrow(py).start - range.start))
-            .unzip();
-        let block_sizes = block_sizes.into_iter().join(",");
-        let block_starts = block_starts.into_iter().join(",");
-
-        format!(
-            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-            contig, range.start, range.end, name, score, strand, range.start, range.end, color,
-            self.segments.len() * 2, block_sizes, block_starts
-        )
+            .map(|x| {
+                let x = x.borrow(py);
+                x.start..x.end
+            })
+            .collect_vec();
+        repeto::io::bed12(contig, strand, &blocks, name, score, color)
     }
 
     pub fn __len__(&self, py: Python) -> usize {
@@ -281,4 +284,92 @@ impl InvertedRepeat {
         }).collect();
         Ok(InvertedRepeat { segments: segments? })
     }
-}
\ No newline at end of file
+}
+
+#[pyclass(get_all, module = "repeto")]
+#[derive(Clone)]
+pub struct Validation {
+    complementarity: Vec<f64>,
+    noncanonical: usize,
+    mismatches: Vec<(isize, isize)>,
+}
+
+#[pymethods]
+impl Validation {
+    pub fn is_valid(&self) -> bool { self.mismatches.is_empty() }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "Validation {{ complementarity: {:?}, noncanonical: {}, mismatches: {:?} }}",
+            self.complementarity, self.noncanonical, self.mismatches
+        )
+    }
+}
+
+
+#[pyclass(get_all, module = "repeto")]
+#[derive(Clone)]
+pub struct GenomicRepeat {
+    contig: String,
+    strand: String,
+    repeat: Py<InvertedRepeat>,
+}
+
+#[pymethods]
+impl GenomicRepeat {
+    #[new]
+    pub fn new(contig: String, strand: String, repeat: Py<InvertedRepeat>) -> PyResult<Self> {
+        Strand::try_from(strand.as_str()).map_err(PyValueError::new_err)?;
+        Ok(Self { contig, strand, repeat })
+    }
+
+    #[staticmethod]
+    pub fn from_window(
+        py: Python, contig: String, strand: String, start: isize, end: isize, repeat: &InvertedRepeat,
+    ) -> PyResult<Self> {
+        let rs = Strand::try_from(strand.as_str()).map_err(PyValueError::new_err)?;
+        let mapped = repeats::genomic::GenomicRepeat::from_window(contig, rs, start..end, repeat.to_rs(py));
+
+        let (contig, _, repeat) = mapped.dissolve();
+        let repeat = Py::new(py, InvertedRepeat::from_rs(&repeat, py)?)?;
+        Ok(Self { contig, strand, repeat })
+    }
+
+    #[pyo3(signature = (*, name = ".", score = 0, color = "0,0,0"))]
+    pub fn to_bed12(&self, py: Python, name: &str, score: u16, color: &str) -> String {
+        let repeat = self.repeat.borrow(py);
+        repeat.to_bed12(py, &self.contig, PyTuple::empty(py), name, score, &self.strand, color)
+    }
+
+    pub fn __repr__(&self, py: Python) -> String {
+        let brange = self.repeat.borrow(py).brange(py);
+        format!("GenomicRepeat {{ {}:{}[{}] }}", self.contig, brange.__repr__(), self.strand)
+    }
+
+    pub fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(match op {
+            CompareOp::Eq => (
+                self.contig == other.contig && self.strand == other.strand &&
+                    self.repeat.borrow(py).__richcmp__(&other.repeat.borrow(py), op, py)?.is_true(py)?
+            ).into_py(py),
+            CompareOp::Ne => (
+                !self.__richcmp__(other, CompareOp::Eq, py)?.is_true(py)?
+            ).into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
+    pub fn __getnewargs__(&self) -> PyResult<(&str, &str, &Py<InvertedRepeat>)> {
+        Ok((&self.contig, &self.strand, &self.repeat))
+    }
+
+    pub fn __traverse__(&self, visit: PyVisit<'_>) -> Result<(), PyTraverseError> {
+        visit.call(&self.repeat)?;
+        Ok(())
+    }
+
+    pub fn __clear__(&mut self) {}
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
@@ -153,3 +153,135 @@ def test_bed12(segments, start, end, blocks, sizes, starts):
                                        f"\t{blocks}\t{sizes}\t{starts}"
 
                             assert bed12 == expected, (name, score, strand, color)
+
+
+def test_genomic_repeat():
+    repeat, _ = _make_ir(([(0, 3), (10, 13)], [(4, 5), (8, 9)]))
+
+    forward = rpt.GenomicRepeat.from_window("1", "+", 100, 120, repeat)
+    assert forward.contig == "1" and forward.strand == "+"
+    assert forward.repeat.brange() == rpt.Range(100, 113)
+    assert repeat.brange() == rpt.Range(0, 13)
+    assert forward.to_bed12() == "1\t100\t113\t.\t0\t+\t100\t113\t0,0,0\t4\t3,1,1,3\t0,4,8,10"
+
+    reverse = rpt.GenomicRepeat.from_window("1", "-", 100, 120, repeat)
+    expected, _ = _make_ir(([(107, 110), (117, 120)], [(111, 112), (115, 116)]))
+    assert reverse.repeat == expected
+    assert reverse.to_bed12(name="IR", score=10) == \
+           "1\t107\t120\tIR\t10\t-\t107\t120\t0,0,0\t4\t3,1,1,3\t0,4,8,10"
+
+    assert reverse == rpt.GenomicRepeat("1", "-", expected)
+    assert reverse != rpt.GenomicRepeat("1", "+", expected)
+    assert reverse == pickle.loads(pickle.dumps(reverse))
+
+    with pytest.raises(ValueError):
+        rpt.GenomicRepeat("1", "*", expected)
+
+
+def test_validate():
+    repeat, _ = _make_ir(([(0, 3), (11, 14)], [(5, 7), (9, 11)]))
+    seq = b"AGCtaGGGuuCgct"
+
+    result = repeat.validate(seq)
+    assert result.complementarity == [1.0, 0.5]
+    assert result.noncanonical == 0
+    assert result.mismatches == [(6, 9)]
+    assert not result.is_valid()
+
+    result = repeat.validate(seq, wobble=True)
+    assert result.complementarity == [1.0, 1.0]
+    assert result.noncanonical == 1
+    assert result.is_valid()
+
+
+def test_compare():
+    reference, _ = _make_ir(IRS["two-blocks"])
+    cmp = rpt.compare([reference], [reference], 40)
+    assert cmp.basepairs.total_reference == cmp.basepairs.matched_reference == 12
+    assert cmp.basepairs.sensitivity == cmp.basepairs.ppv == cmp.basepairs.f1 == cmp.basepairs.mcc == 1
+    assert cmp.arms.f1 == 1
+
+    # Right arm is shifted by 1nt
+    predicted, _ = _make_ir(([(0, 10), (21, 31)],))
+    cmp = rpt.compare([predicted], [reference], 40)
+    assert cmp.basepairs.matched_predicted == 0 and cmp.basepairs.f1 == 0
+    assert cmp.arms.matched_reference == 1
+
+    cmp = rpt.compare([predicted], [reference], 40, slippage=True)
+    assert cmp.basepairs.matched_predicted == 10 and cmp.basepairs.matched_reference == 10
+    assert cmp.basepairs.ppv == 1 and cmp.basepairs.sensitivity == 10 / 12
+    # TP = 10, FP = 0, FN = 2, TN = 40 * 39 / 2 - 12 = 768
+    assert abs(cmp.basepairs.mcc - 10 * 768 / (10 * 12 * 768 * 770) ** 0.5) < 1e-12
+
+    cmp = rpt.compare([], [reference], 40)
+    assert cmp.basepairs.sensitivity == cmp.arms.sensitivity == 0
+
+
+def test_optimize():
+    outer, _ = _make_ir(([(0, 5), (30, 35)],))
+    inner, _ = _make_ir(([(10, 12), (20, 22)],))
+    crossing, _ = _make_ir(([(6, 7), (15, 16)],))
+    ir = [outer, inner, crossing]
+
+    solution, score = rpt.optimize(ir, [1, 2, 1])
+    assert solution == [outer, inner] and score == 3 and isinstance(score, int)
+
+    solution, score = rpt.optimize(ir, [1.5, 0.25, 0.5])
+    assert solution == [outer, crossing] and score == 2.0 and isinstance(score, float)
+
+    with pytest.raises(ValueError):
+        rpt.optimize(ir, [1.0, float("nan"), 0.5])
+
+
+def test_optimize_lexicographic():
+    outer, _ = _make_ir(([(0, 5), (30, 35)],))
+    inner, _ = _make_ir(([(10, 12), (20, 22)],))
+    crossing, _ = _make_ir(([(6, 7), (15, 16)],))
+    ir = [outer, inner, crossing]
+
+    # Ties in the primary objective are broken by the secondary one
+    solution, score = rpt.optimize(ir, [(1, 0), (1, 5), (1, 1)])
+    assert solution == [outer, inner] and score == (2, 5)
+
+    solution, score = rpt.optimize(ir, [(1, 0.5, 0), (0, 0.25, 9), (1, 0.5, 1)])
+    assert solution == [outer, crossing] and score == (2.0, 1.0, 1.0)
+
+    with pytest.raises(ValueError):
+        rpt.optimize(ir, [(1.0, 0.0), (1.0, float("nan")), (1.0, 0.0)])
+
+
+def test_optimize_groups():
+    outer, _ = _make_ir(([(0, 5), (30, 35)],))
+    inner, _ = _make_ir(([(10, 12), (20, 22)],))
+    crossing, _ = _make_ir(([(6, 7), (15, 16)],))
+    ir = [outer, inner, crossing, outer, inner, crossing]
+    groups = [("chr1", "+"), ("chr1", "+"), ("chr1", "+"), "chr2", "chr2", "chr2"]
+
+    for parallel in False, True:
+        result = rpt.optimize_groups(ir, [1, 2, 1, 1, 1, 3], groups, parallel=parallel)
+        assert list(result) == [("chr1", "+"), "chr2"]
+        assert result[("chr1", "+")] == ([outer, inner], 3)
+        assert result["chr2"] == ([outer, crossing], 4)
+
+    assert rpt.optimize_groups([], [], []) == {}
+    with pytest.raises(ValueError):
+        rpt.optimize_groups(ir, [1] * 6, groups[:-1])
+    with pytest.raises(ValueError):
+        rpt.optimize_groups(ir, [1.0, float("nan"), 0.5, 1.0, 1.0, 1.0], groups)
+
+
+def test_nest():
+    outer, _ = _make_ir(([(0, 2), (30, 32)], [(7, 9), (25, 27)]))
+    first, _ = _make_ir(([(3, 4), (5, 6)],))
+    second, _ = _make_ir(([(10, 12), (19, 21)],))
+    inner, _ = _make_ir(([(13, 14), (15, 16)],))
+    sibling, _ = _make_ir(([(40, 45), (50, 55)],))
+
+    solution, _ = rpt.optimize([sibling, inner, second, first, outer], [1, 1, 1, 1, 1])
+    tree = rpt.nest(solution)
+    assert [x.repeat for x in tree] == [outer, sibling]
+    assert tree[0].gap is None and tree[1].children == []
+
+    children = tree[0].children
+    assert [(x.repeat, x.gap) for x in children] == [(first, 0), (second, 1)]
+    assert [(x.repeat, x.gap) for x in children[1].children] == [(inner, 0)]
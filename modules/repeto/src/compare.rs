@@ -0,0 +1,339 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::Range;
+
+use derive_getters::Getters;
+
+use super::repeats::inv;
+
+#[derive(Debug, Clone, PartialEq, Getters)]
+pub struct BasePairMetrics {
+    // Reference pairs recovered by the prediction
+    matched_reference: usize,
+    // Predicted pairs supported by the reference
+    matched_predicted: usize,
+    total_reference: usize,
+    total_predicted: usize,
+    sensitivity: f64,
+    ppv: f64,
+    f1: f64,
+    // Matthews correlation coefficient over all possible pairs of sequence positions
+    mcc: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Getters)]
+pub struct ArmMetrics {
+    // Reference repeats whose both arms are overlapped by arms of a single predicted repeat
+    matched_reference: usize,
+    // Predicted repeats whose both arms overlap arms of a single reference repeat
+    matched_predicted: usize,
+    total_reference: usize,
+    total_predicted: usize,
+    sensitivity: f64,
+    ppv: f64,
+    f1: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Getters)]
+pub struct Comparison {
+    basepairs: BasePairMetrics,
+    arms: ArmMetrics,
+}
+
+/// Compare predicted repeats against the reference ones.
+///
+/// Base pair metrics follow the conventions of RNA secondary structure benchmarks: with `slippage`,
+/// a pair (i, j) is considered present if any of (i, j), (i ± 1, j), (i, j ± 1) is present in the
+/// other set. MCC is computed over all length * (length - 1) / 2 pairs of sequence positions, where
+/// true positives are pairs of a one-to-one matching between predicted and reference pairs (exact matches
+/// first, then the maximum number of slipped ones), false positives and false negatives are unmatched
+/// predicted and reference pairs. All ratios with zero denominators are reported as 0.
+pub fn run<Idx, P, R>(predicted: &[P], reference: &[R], length: usize, slippage: bool) -> Comparison
+    where
+        Idx: inv::Coordinate + Hash,
+        P: Borrow<inv::Repeat<Idx>>,
+        R: Borrow<inv::Repeat<Idx>>
+{
+    Comparison {
+        basepairs: basepairs(predicted, reference, length, slippage),
+        arms: arms(predicted, reference),
+    }
+}
+
+pub fn basepairs<Idx, P, R>(predicted: &[P], reference: &[R], length: usize, slippage: bool) -> BasePairMetrics
+    where
+        Idx: inv::Coordinate + Hash,
+        P: Borrow<inv::Repeat<Idx>>,
+        R: Borrow<inv::Repeat<Idx>>
+{
+    let predicted: HashSet<_> = predicted.iter().flat_map(|x| x.borrow().basepairs()).collect();
+    let reference: HashSet<_> = reference.iter().flat_map(|x| x.borrow().basepairs()).collect();
+
+    let matched_reference = matched(&reference, &predicted, slippage);
+    let matched_predicted = matched(&predicted, &reference, slippage);
+
+    let sensitivity = ratio(matched_reference, reference.len());
+    let ppv = ratio(matched_predicted, predicted.len());
+
+    // A single predicted pair might support several reference pairs with slippage => count each pair once
+    let tp = one_to_one(&predicted, &reference, slippage);
+    let (fp, fn_) = (predicted.len() - tp, reference.len() - tp);
+    let tn = (length * length.saturating_sub(1) / 2).saturating_sub(tp + fp + fn_);
+
+    BasePairMetrics {
+        matched_reference,
+        matched_predicted,
+        total_reference: reference.len(),
+        total_predicted: predicted.len(),
+        sensitivity,
+        ppv,
+        f1: f1(sensitivity, ppv),
+        mcc: mcc(tp, fp, fn_, tn),
+    }
+}
+
+pub fn arms<Idx, P, R>(predicted: &[P], reference: &[R]) -> ArmMetrics
+    where
+        Idx: inv::Coordinate,
+        P: Borrow<inv::Repeat<Idx>>,
+        R: Borrow<inv::Repeat<Idx>>
+{
+    let mut predhits = vec![false; predicted.len()];
+    let mut refhits = vec![false; reference.len()];
+
+    // Sort predictions by the left arm start to limit the number of checked pairs
+    let mut order = (0..predicted.len()).collect::<Vec<_>>();
+    order.sort_by_key(|x| predicted[*x].borrow().left_brange().start);
+    let starts = order.iter().map(|x| predicted[*x].borrow().left_brange().start).collect::<Vec<_>>();
+    let maxlen = predicted.iter()
+        .map(|x| {
+            let arm = x.borrow().left_brange();
+            arm.end - arm.start
+        })
+        .max()
+        .unwrap_or_else(Idx::zero);
+
+    for (refind, r) in reference.iter().enumerate() {
+        let (rleft, rright) = (r.borrow().left_brange(), r.borrow().right_brange());
+
+        // Predicted left arms must start in [rleft.start - maxlen, rleft.end) to overlap the reference left arm
+        let lo = starts.partition_point(|x| *x + maxlen <= rleft.start);
+        let hi = starts.partition_point(|x| *x < rleft.end);
+        for &predind in &order[lo..hi] {
+            let p = predicted[predind].borrow();
+            if overlaps(&p.left_brange(), &rleft) && overlaps(&p.right_brange(), &rright) {
+                predhits[predind] = true;
+                refhits[refind] = true;
+            }
+        }
+    }
+
+    let matched_reference = refhits.into_iter().filter(|x| *x).count();
+    let matched_predicted = predhits.into_iter().filter(|x| *x).count();
+    let sensitivity = ratio(matched_reference, reference.len());
+    let ppv = ratio(matched_predicted, predicted.len());
+
+    ArmMetrics {
+        matched_reference,
+        matched_predicted,
+        total_reference: reference.len(),
+        total_predicted: predicted.len(),
+        sensitivity,
+        ppv,
+        f1: f1(sensitivity, ppv),
+    }
+}
+
+fn matched<Idx: inv::Coordinate + Hash>(
+    query: &HashSet<(Idx, Idx)>, target: &HashSet<(Idx, Idx)>, slippage: bool,
+) -> usize {
+    query.iter().filter(|pair| {
+        target.contains(pair) || (slippage && slipped(**pair).into_iter().any(|x| target.contains(&x)))
+    }).count()
+}
+
+// Pairs shifted by one nucleotide on either side
+fn slipped<Idx: inv::Coordinate>((i, j): (Idx, Idx)) -> Vec<(Idx, Idx)> {
+    let one = Idx::one();
+    // Unsigned coordinates can't go below zero
+    let candidates = [
+        (i.checked_sub(&one), Some(j)), (i.checked_add(&one), Some(j)),
+        (Some(i), j.checked_sub(&one)), (Some(i), j.checked_add(&one)),
+    ];
+    candidates.into_iter()
+        .filter_map(|x| match x {
+            (Some(i), Some(j)) => Some((i, j)),
+            _ => None
+        })
+        .collect()
+}
+
+// Size of a one-to-one matching between predicted and reference pairs. Exact matches are always kept, and the
+// remaining pairs are matched through slippage by augmenting paths, i.e. the number of slipped matches is maximal.
+fn one_to_one<Idx: inv::Coordinate + Hash>(
+    predicted: &HashSet<(Idx, Idx)>, reference: &HashSet<(Idx, Idx)>, slippage: bool,
+) -> usize {
+    let exact = predicted.intersection(reference).count();
+    if !slippage {
+        return exact;
+    }
+
+    let free = reference.difference(predicted).copied().collect::<Vec<_>>();
+    let position: HashMap<_, _> = free.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+    let candidates = predicted.difference(reference)
+        .map(|x| slipped(*x).into_iter().filter_map(|y| position.get(&y).copied()).collect::<Vec<_>>())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+
+    // Reference pair -> predicted pair it's matched to
+    let mut owner = vec![None; free.len()];
+    let mut visited = vec![usize::MAX; free.len()];
+    let mut slipped = 0;
+    for start in 0..candidates.len() {
+        // Path of (predicted pair, index of the next candidate to try)
+        let mut path = vec![(start, 0)];
+        while let Some(&(query, next)) = path.last() {
+            let target = match candidates[query].get(next) {
+                Some(&x) => x,
+                None => {
+                    path.pop();
+                    continue;
+                }
+            };
+            path.last_mut().unwrap().1 += 1;
+            if visited[target] == start {
+                continue;
+            }
+            visited[target] = start;
+
+            match owner[target] {
+                Some(other) => path.push((other, 0)),
+                None => {
+                    // Each predicted pair on the path takes the last tried candidate
+                    for &(query, next) in &path {
+                        owner[candidates[query][next - 1]] = Some(query);
+                    }
+                    slipped += 1;
+                    break;
+                }
+            }
+        }
+    }
+    exact + slipped
+}
+
+fn overlaps<Idx: inv::Coordinate>(a: &Range<Idx>, b: &Range<Idx>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 }
+}
+
+fn f1(sensitivity: f64, ppv: f64) -> f64 {
+    if sensitivity + ppv == 0.0 { 0.0 } else { 2.0 * sensitivity * ppv / (sensitivity + ppv) }
+}
+
+fn mcc(tp: usize, fp: usize, fn_: usize, tn: usize) -> f64 {
+    let (tp, fp, fn_, tn) = (tp as f64, fp as f64, fn_ as f64, tn as f64);
+    let denominator = ((tp + fp) * (tp + fn_) * (tn + fp) * (tn + fn_)).sqrt();
+    if denominator == 0.0 { 0.0 } else { (tp * tn - fp * fn_) / denominator }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeats(data: Vec<Vec<(Range<isize>, Range<isize>)>>) -> Vec<inv::Repeat<isize>> {
+        data.into_iter()
+            .map(|x| inv::Repeat::new(x.into_iter().map(|s| s.into()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn identical() {
+        let ir = repeats(vec![
+            vec![(0..5, 10..15)],
+            vec![(20..22, 40..42), (25..27, 30..32)],
+        ]);
+        let cmp = run(&ir, &ir, 50, false);
+
+        assert_eq!(cmp.basepairs().total_reference, 9);
+        assert_eq!(cmp.basepairs().matched_reference, 9);
+        for x in [cmp.basepairs().sensitivity, cmp.basepairs().ppv, cmp.basepairs().f1, cmp.basepairs().mcc] {
+            assert_eq!(x, 1.0);
+        }
+        assert_eq!(cmp.arms().f1, 1.0);
+    }
+
+    #[test]
+    fn empty() {
+        let ir = repeats(vec![vec![(0..5, 10..15)]]);
+        let none: Vec<inv::Repeat<isize>> = vec![];
+
+        let cmp = run(&none, &ir, 20, true);
+        assert_eq!((cmp.basepairs().sensitivity, cmp.basepairs().ppv, cmp.basepairs().f1), (0.0, 0.0, 0.0));
+        assert_eq!((cmp.arms().matched_reference, cmp.arms().total_reference), (0, 1));
+
+        let cmp = run(&none, &none, 20, true);
+        assert_eq!(cmp.basepairs().mcc, 0.0);
+    }
+
+    #[test]
+    fn slippage() {
+        // Same stem shifted by one nucleotide on the right arm
+        let reference = repeats(vec![vec![(0..4, 10..14)]]);
+        let predicted = repeats(vec![vec![(0..4, 11..15)]]);
+
+        let strict = basepairs(&predicted, &reference, 20, false);
+        assert_eq!((strict.matched_reference, strict.matched_predicted), (0, 0));
+
+        let relaxed = basepairs(&predicted, &reference, 20, true);
+        assert_eq!((relaxed.matched_reference, relaxed.matched_predicted), (4, 4));
+        assert_eq!((relaxed.f1, relaxed.mcc), (1.0, 1.0));
+
+        // A single predicted pair supports both reference pairs, but it's counted once for MCC
+        let reference = repeats(vec![vec![(5..6, 10..11)], vec![(5..6, 12..13)]]);
+        let predicted = repeats(vec![vec![(5..6, 11..12)]]);
+        let relaxed = basepairs(&predicted, &reference, 20, true);
+        assert_eq!((relaxed.matched_reference, relaxed.matched_predicted), (2, 1));
+        // TP = 1, FP = 0, FN = 1, TN = 20 * 19 / 2 - 2 = 188
+        assert!((relaxed.mcc - 188.0 / (2.0f64 * 188.0 * 189.0).sqrt()).abs() < 1e-12);
+
+        // The second predicted pair can only take (5, 10) => the first one must switch to (5, 12)
+        let predicted = repeats(vec![vec![(5..6, 11..12)], vec![(4..5, 10..11)]]);
+        assert_eq!(basepairs(&predicted, &reference, 20, true).mcc, 1.0);
+    }
+
+    #[test]
+    fn partial() {
+        let reference = repeats(vec![
+            vec![(0..4, 10..14)],
+            vec![(20..24, 30..34)],
+        ]);
+        let predicted = repeats(vec![
+            vec![(0..2, 12..14)],
+            vec![(50..52, 60..62)],
+            vec![(21..23, 40..42)],
+        ]);
+
+        let bp = basepairs(&predicted, &reference, 70, false);
+        assert_eq!((bp.matched_reference, bp.total_reference), (2, 8));
+        assert_eq!((bp.matched_predicted, bp.total_predicted), (2, 6));
+        assert_eq!(bp.sensitivity, 0.25);
+        assert_eq!(bp.ppv, 1.0 / 3.0);
+
+        // TP = 2, FP = 4, FN = 6, TN = 70 * 69 / 2 - 12 = 2403
+        // MCC = (2 * 2403 - 4 * 6) / sqrt(6 * 8 * 2407 * 2409)
+        assert!((bp.mcc - 0.28663717040443737).abs() < 1e-12);
+        // More true negatives move MCC towards sqrt(sensitivity * ppv) but never reach it
+        let longer = basepairs(&predicted, &reference, 1000, false).mcc;
+        assert!(bp.mcc < longer && longer < (bp.sensitivity * bp.ppv).sqrt());
+
+        let arms = arms(&predicted, &reference);
+        assert_eq!((arms.matched_reference, arms.matched_predicted), (1, 1));
+        assert_eq!(arms.sensitivity, 0.5);
+        assert_eq!(arms.ppv, 1.0 / 3.0);
+    }
+}
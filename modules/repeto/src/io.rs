@@ -0,0 +1,23 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use super::repeats::Coordinate;
+
+/// Format sorted, non-overlapping sequence blocks as a BED12 record.
+pub fn bed12<Idx: Coordinate + Display>(
+    contig: &str, strand: &str, blocks: &[Range<Idx>], name: &str, score: u16, color: &str,
+) -> String {
+    assert!(!blocks.is_empty(), "BED12 record must have at least one block");
+    assert!(score <= 1000, "Score must be from 0 to 1000");
+
+    let (start, end) = (blocks[0].start, blocks[blocks.len() - 1].end);
+    let sizes = blocks.iter().map(|x| x.end - x.start).join(",");
+    let starts = blocks.iter().map(|x| x.start - start).join(",");
+
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        contig, start, end, name, score, strand, start, end, color, blocks.len(), sizes, starts
+    )
+}
@@ -1,3 +1,6 @@
 pub mod repeats;
 pub mod optimize;
 pub mod predict;
+pub mod compare;
+pub mod io;
+pub mod liftover;
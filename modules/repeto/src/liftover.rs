@@ -0,0 +1,179 @@
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+
+use derive_getters::{Dissolve, Getters};
+use itertools::Itertools;
+
+use super::io;
+use super::repeats::Coordinate;
+use super::repeats::genomic::Strand;
+use super::repeats::inv;
+
+/// Spliced transcript defined by its exons in genome coordinates.
+///
+/// Transcript coordinates start at the 5' end, i.e. position 0 is the last exon nucleotide for reverse strand
+/// transcripts.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Getters, Dissolve)]
+pub struct Transcript<Idx: Coordinate> {
+    contig: String,
+    strand: Strand,
+    exons: Vec<Range<Idx>>,
+}
+
+impl<Idx: Coordinate + Debug> Transcript<Idx> {
+    pub fn new(contig: String, strand: Strand, mut exons: Vec<Range<Idx>>) -> Self {
+        assert!(!exons.is_empty(), "Transcript must have at least one exon");
+        exons.sort_by_key(|x| x.start);
+        for (prv, nxt) in exons.iter().tuple_windows() {
+            assert!(prv.start < prv.end, "Exon start must be < end: {prv:?}");
+            assert!(prv.end <= nxt.start, "Exons must not overlap: {prv:?} vs {nxt:?}");
+        }
+        let last = exons.last().unwrap();
+        assert!(last.start < last.end, "Exon start must be < end: {last:?}");
+
+        Self { contig, strand, exons }
+    }
+
+    pub fn len(&self) -> Idx {
+        let mut length = Idx::zero();
+        for exon in &self.exons {
+            length += exon.end - exon.start;
+        }
+        length
+    }
+
+    /// Map a range in transcript coordinates to genome blocks sorted by their genomic start.
+    pub fn map(&self, range: &Range<Idx>) -> Vec<Range<Idx>> {
+        assert!(
+            Idx::zero() <= range.start && range.start < range.end && range.end <= self.len(),
+            "Range {range:?} is outside of the transcript with length {:?}", self.len()
+        );
+
+        let mut blocks = Vec::new();
+        let mut offset = Idx::zero();
+        let exons: Box<dyn Iterator<Item=&Range<Idx>>> = match self.strand {
+            Strand::Reverse => Box::new(self.exons.iter().rev()),
+            Strand::Forward | Strand::Unknown => Box::new(self.exons.iter()),
+        };
+        for exon in exons {
+            let length = exon.end - exon.start;
+            // Overlap between the range and the current exon in transcript coordinates
+            let (start, end) = (range.start.max(offset), range.end.min(offset + length));
+            if start < end {
+                let (start, end) = (start - offset, end - offset);
+                blocks.push(match self.strand {
+                    Strand::Reverse => (exon.end - end)..(exon.end - start),
+                    Strand::Forward | Strand::Unknown => (exon.start + start)..(exon.start + end),
+                });
+            }
+            offset += length;
+            if offset >= range.end {
+                break;
+            }
+        }
+
+        if self.strand == Strand::Reverse {
+            blocks.reverse();
+        }
+        blocks
+    }
+
+    /// Map a repeat from transcript coordinates to the genome, splitting its arms at exon junctions.
+    pub fn liftover(&self, repeat: &inv::Repeat<Idx>) -> SplicedRepeat<Idx> {
+        let segments = repeat.segments().iter().map(|x| {
+            let (left, right) = (self.map(x.left()), self.map(x.right()));
+            // Transcript left arms are downstream in the genome for reverse strand transcripts
+            match self.strand {
+                Strand::Reverse => SplicedSegment { left: right, right: left },
+                Strand::Forward | Strand::Unknown => SplicedSegment { left, right },
+            }
+        }).collect();
+
+        SplicedRepeat { contig: self.contig.clone(), strand: self.strand, segments }
+    }
+}
+
+/// Complementary arms of a repeat segment, each represented by one or more genome blocks.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Getters, Dissolve)]
+pub struct SplicedSegment<Idx: Coordinate> {
+    left: Vec<Range<Idx>>,
+    right: Vec<Range<Idx>>,
+}
+
+/// Inverted repeat lifted over from a spliced transcript to the genome. Segments are ordered from outer to inner.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Getters, Dissolve)]
+pub struct SplicedRepeat<Idx: Coordinate> {
+    contig: String,
+    strand: Strand,
+    segments: Vec<SplicedSegment<Idx>>,
+}
+
+impl<Idx: Coordinate> SplicedRepeat<Idx> {
+    pub fn blocks(&self) -> Vec<Range<Idx>> {
+        self.segments.iter()
+            .flat_map(|x| x.left.iter())
+            .chain(self.segments.iter().rev().flat_map(|x| x.right.iter()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn to_bed12(&self, name: &str, score: u16, color: &str) -> String
+        where Idx: Display
+    {
+        io::bed12(&self.contig, &self.strand.to_string(), &self.blocks(), name, score, color)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+
+    fn transcript(strand: Strand) -> Transcript<isize> {
+        // Transcript length = 10 + 5 + 10 = 25
+        Transcript::new("chr1".to_string(), strand, vec![200..210, 100..110, 150..155])
+    }
+
+    #[test]
+    fn map() {
+        let forward = transcript(Strand::Forward);
+        assert_eq!(forward.len(), 25);
+        assert_eq!(forward.map(&(0..5)), vec![100..105]);
+        assert_eq!(forward.map(&(8..17)), vec![108..110, 150..155, 200..202]);
+        assert_eq!(forward.map(&(15..25)), vec![200..210]);
+
+        let reverse = transcript(Strand::Reverse);
+        assert_eq!(reverse.map(&(0..5)), vec![205..210]);
+        assert_eq!(reverse.map(&(8..17)), vec![108..110, 150..155, 200..202]);
+        assert_eq!(reverse.map(&(15..25)), vec![100..110]);
+    }
+
+    #[test]
+    fn liftover() {
+        let repeat = inv::Repeat::new(vec![
+            inv::Segment::new(0..4, 20..24),
+            inv::Segment::new(8..12, 14..18),
+        ]);
+
+        let lifted = transcript(Strand::Forward).liftover(&repeat);
+        assert_eq!(lifted.blocks(), vec![100..104, 108..110, 150..152, 154..155, 200..203, 205..209]);
+        assert_eq!(
+            lifted.to_bed12(".", 0, "0,0,0"),
+            "chr1\t100\t209\t.\t0\t+\t100\t209\t0,0,0\t6\t4,2,2,1,3,4\t0,8,50,54,100,105"
+        );
+
+        let lifted = transcript(Strand::Reverse).liftover(&repeat);
+        assert_eq!(lifted.segments()[0], SplicedSegment { left: vec![101..105], right: vec![206..210] });
+        assert_eq!(
+            lifted.segments()[1],
+            SplicedSegment { left: vec![107..110, 150..151], right: vec![153..155, 200..202] }
+        );
+        assert_eq!(lifted.blocks(), vec![101..105, 107..110, 150..151, 153..155, 200..202, 206..210]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn outside() {
+        transcript(Strand::Forward).map(&(20..26));
+    }
+}
@@ -0,0 +1,129 @@
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use super::conflict::conflict;
+use super::{inv, score};
+
+/// Strategy used to find a coherent set of repeats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strategy<Idx: inv::Coordinate> {
+    /// Exact dynamic programming, same as `run`
+    Exact,
+    /// Accept repeats by decreasing score if they are coherent with already accepted ones
+    Greedy,
+    /// Exact dynamic programming in overlapping windows sliding from left to right.
+    /// Repeats with bounding ranges longer than the window are never selected.
+    Windowed { size: Idx, overlap: Idx },
+}
+
+// Blocks of accepted repeats. Accepted repeats never share nucleotides, i.e. blocks are keyed by their start.
+// Repeats without blocks inside the bounding range of a candidate are always coherent with it: they are either
+// located outside the candidate or the candidate fits into one of their gaps.
+struct Accepted<Idx: inv::Coordinate> {
+    blocks: BTreeMap<Idx, (Idx, usize)>,
+}
+
+impl<Idx: inv::Coordinate> Accepted<Idx> {
+    fn new() -> Self { Self { blocks: BTreeMap::new() } }
+
+    fn coherent<IR: Borrow<inv::Repeat<Idx>>>(&self, ir: &[IR], rnaid: usize) -> bool {
+        let repeat = ir[rnaid].borrow();
+        let brange = repeat.brange();
+
+        // The only block that starts before the range and might still overlap it
+        let before = self.blocks.range(..brange.start).next_back().filter(|(_, (end, _))| *end > brange.start);
+        before.into_iter()
+            .chain(self.blocks.range(brange.start..brange.end))
+            .map(|(_, (_, x))| *x)
+            .unique()
+            .all(|x| conflict(repeat, ir[x].borrow()).is_none())
+    }
+
+    fn insert<IR: Borrow<inv::Repeat<Idx>>>(&mut self, ir: &[IR], rnaid: usize) {
+        for block in ir[rnaid].borrow().seqranges() {
+            self.blocks.insert(block.start, (block.end, rnaid));
+        }
+    }
+}
+
+pub fn greedy<Idx, IR, Score>(ir: &[IR], scores: &[Score]) -> (Vec<usize>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let order = (0..ir.len())
+        .filter(|x| scores[*x].exceeds(Score::zero()))
+        .sorted_by(|a, b| scores[*b].partial_cmp(&scores[*a]).unwrap().then(a.cmp(b)));
+
+    let mut accepted = Accepted::new();
+    let (mut selected, mut score) = (Vec::new(), Score::zero());
+    for rnaid in order {
+        if accepted.coherent(ir, rnaid) {
+            accepted.insert(ir, rnaid);
+            selected.push(rnaid);
+            score = score.add(scores[rnaid]);
+        }
+    }
+    selected.sort();
+    (selected, score)
+}
+
+// Each window starts at the first pending repeat. Selected repeats that start before the next window are committed,
+// other repeats that were considered and start before the next window are dropped.
+pub fn windowed<Idx, IR, Score>(ir: &[IR], scores: &[Score], size: Idx, overlap: Idx) -> (Vec<usize>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let step = size - overlap;
+    let order = (0..ir.len())
+        .filter(|x| scores[*x].exceeds(Score::zero()))
+        .filter(|x| {
+            let brange = ir[*x].borrow().brange();
+            brange.end - brange.start <= size
+        })
+        .sorted_by_key(|x| ir[*x].borrow().brange().start)
+        .collect_vec();
+
+    let mut accepted = Accepted::new();
+    let mut pending = vec![true; order.len()];
+    let (mut selected, mut score) = (Vec::new(), Score::zero());
+    let mut first = 0;
+    while first < order.len() {
+        let start = ir[order[first]].borrow().brange().start;
+        let (end, commit) = (start + size, start + step);
+
+        let candidates = (first..order.len())
+            .take_while(|x| ir[order[*x]].borrow().brange().start < end)
+            .filter(|x| pending[*x] && ir[order[*x]].borrow().brange().end <= end)
+            .collect_vec();
+        for &x in &candidates {
+            if ir[order[x]].borrow().brange().start < commit {
+                pending[x] = false;
+            }
+        }
+
+        let coherent = candidates.into_iter().filter(|x| accepted.coherent(ir, order[*x])).collect_vec();
+        let subset = coherent.iter().map(|x| ir[order[*x]].borrow()).collect_vec();
+        let subscores = coherent.iter().map(|x| scores[order[*x]]).collect_vec();
+        let (solution, _) = super::run(&subset, &subscores);
+
+        for rnaid in solution.into_iter().map(|x| order[coherent[x]]) {
+            if ir[rnaid].borrow().brange().start < commit {
+                accepted.insert(ir, rnaid);
+                selected.push(rnaid);
+                score = score.add(scores[rnaid]);
+            }
+        }
+
+        while first < order.len() && !pending[first] {
+            first += 1;
+        }
+    }
+    selected.sort();
+    (selected, score)
+}
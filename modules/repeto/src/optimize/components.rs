@@ -0,0 +1,70 @@
+use std::borrow::Borrow;
+
+use itertools::Itertools;
+
+use super::{dynprog, inv, score};
+
+// Clusters of positive repeats with overlapping bounding ranges, ordered by their start. Repeats from different
+// clusters never interact, i.e. each cluster can be optimized independently. Non-positive repeats are never
+// included in the optimum and are skipped to avoid merging otherwise independent clusters.
+pub fn clusters<Idx, IR, Score>(ir: &[IR], scores: &[Score]) -> Vec<Vec<usize>>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let order = (0..ir.len())
+        .filter(|x| scores[*x].exceeds(Score::zero()))
+        .sorted_by_key(|x| ir[*x].borrow().brange().start);
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut end = None;
+    for rnaid in order {
+        let brange = ir[rnaid].borrow().brange();
+        match end {
+            Some(x) if brange.start < x => {
+                clusters.last_mut().unwrap().push(rnaid);
+                end = Some(brange.end.max(x));
+            }
+            _ => {
+                clusters.push(vec![rnaid]);
+                end = Some(brange.end);
+            }
+        }
+    }
+
+    // Keep the original order inside clusters => ties are resolved in the same way as in the global solution
+    for cluster in &mut clusters {
+        cluster.sort();
+    }
+    clusters
+}
+
+pub fn solve<Idx, IR, Score>(ir: &[IR], scores: &[Score], cluster: &[usize], depth: Option<usize>) -> (Vec<usize>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    // Trivial solution
+    if cluster.len() == 1 {
+        return (cluster.to_vec(), scores[cluster[0]]);
+    }
+
+    let subset = cluster.iter().map(|x| ir[*x].borrow()).collect_vec();
+    let subscores = cluster.iter().map(|x| scores[*x]).collect_vec();
+    let mut dp = depth.map_or_else(dynprog::DynProgSolution::new, dynprog::DynProgSolution::with_depth);
+    let (solution, score) = dp.solve(&subset, &subscores);
+    (solution.into_iter().map(|x| cluster[x]).collect(), score)
+}
+
+pub fn merge<Score: score::Score>(results: Vec<(Vec<usize>, Score)>) -> (Vec<usize>, Score) {
+    let mut score = Score::zero();
+    let mut solution = Vec::new();
+    for (s, x) in results {
+        solution.extend(s);
+        score = score.add(x);
+    }
+    solution.sort();
+    (solution, score)
+}
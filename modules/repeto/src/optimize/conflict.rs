@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+use super::inv;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Conflict {
+    // Repeats share at least one nucleotide
+    Overlap,
+    // Repeats don't overlap, but neither of them fits into a single gap of the other
+    Cross,
+}
+
+/// Check whether two repeats can be part of the same coherent set.
+pub fn conflict<Idx: inv::Coordinate>(a: &inv::Repeat<Idx>, b: &inv::Repeat<Idx>) -> Option<Conflict> {
+    let (abr, bbr) = (a.brange(), b.brange());
+    if abr.end <= bbr.start || bbr.end <= abr.start {
+        return None;
+    }
+
+    let ablocks: Vec<_> = a.seqranges().collect();
+    let bblocks: Vec<_> = b.seqranges().collect();
+
+    // Blocks are sorted => a simple merge is enough to find overlaps
+    let (mut i, mut j) = (0, 0);
+    while i < ablocks.len() && j < bblocks.len() {
+        if ablocks[i].start < bblocks[j].end && bblocks[j].start < ablocks[i].end {
+            return Some(Conflict::Overlap);
+        }
+        if ablocks[i].end <= bblocks[j].start {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    if ingap(&ablocks, &bbr) || ingap(&bblocks, &abr) {
+        None
+    } else {
+        Some(Conflict::Cross)
+    }
+}
+
+// Is the range located inside a single gap between the blocks?
+fn ingap<Idx: inv::Coordinate>(blocks: &[&Range<Idx>], range: &Range<Idx>) -> bool {
+    let ind = blocks.partition_point(|x| x.end <= range.start);
+    0 < ind && ind < blocks.len() && range.end <= blocks[ind].start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(segments: Vec<(Range<i32>, Range<i32>)>) -> inv::Repeat<i32> {
+        inv::Repeat::new(segments.into_iter().map(|x| x.into()).collect())
+    }
+
+    #[test]
+    fn conflicts() {
+        let outer = repeat(vec![(0..5, 30..35), (10..12, 20..22)]);
+        for (other, expected) in [
+            // Disjoint
+            (repeat(vec![(35..36, 40..41)]), None),
+            // Nested in gaps
+            (repeat(vec![(5..6, 9..10)]), None),
+            (repeat(vec![(13..15, 17..19)]), None),
+            (repeat(vec![(12..13, 14..15)]), None),
+            (repeat(vec![(23..25, 27..29)]), None),
+            // Enclosing
+            (repeat(vec![(-5..-1, 40..44)]), None),
+            // Overlapping
+            (repeat(vec![(4..6, 7..9)]), Some(Conflict::Overlap)),
+            (repeat(vec![(11..12, 25..26)]), Some(Conflict::Overlap)),
+            // Crossing
+            (repeat(vec![(6..7, 15..16)]), Some(Conflict::Cross)),
+            (repeat(vec![(25..26, 40..41)]), Some(Conflict::Cross)),
+        ] {
+            assert_eq!(conflict(&outer, &other), expected, "{other:?}");
+            assert_eq!(conflict(&other, &outer), expected, "{other:?}");
+        }
+    }
+}
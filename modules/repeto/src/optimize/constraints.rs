@@ -0,0 +1,86 @@
+use std::borrow::Borrow;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+use derive_getters::{Dissolve, Getters};
+
+use super::conflict::conflict;
+use super::inv;
+
+/// Prior knowledge about the structure, e.g. from structure probing experiments.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Getters, Dissolve)]
+pub struct Constraints<Idx: inv::Coordinate> {
+    // Repeats that must be in the solution
+    include: Vec<usize>,
+    // Repeats that must not be in the solution
+    exclude: Vec<usize>,
+    // Sequence ranges that must remain single-stranded
+    unpaired: Vec<Range<Idx>>,
+}
+
+impl<Idx: inv::Coordinate> Constraints<Idx> {
+    pub fn new(include: Vec<usize>, exclude: Vec<usize>, unpaired: Vec<Range<Idx>>) -> Self {
+        Self { include, exclude, unpaired }
+    }
+
+    // Repeats that pair nucleotides in unpaired ranges can't be selected
+    pub(super) fn allows(&self, repeat: &inv::Repeat<Idx>) -> bool {
+        !repeat.seqranges().any(|x| self.unpaired.iter().any(|y| x.start < y.end && y.start < x.end))
+    }
+
+    // Included repeats in the ascending order, if they can be selected together
+    pub(super) fn included<IR: Borrow<inv::Repeat<Idx>>>(&self, ir: &[IR]) -> Result<Vec<usize>, ConstraintError> {
+        assert!(
+            self.include.iter().chain(&self.exclude).all(|x| *x < ir.len()),
+            "Constrained repeats must be valid indices"
+        );
+
+        let mut include = self.include.clone();
+        include.sort();
+        include.dedup();
+
+        for (i, &a) in include.iter().enumerate() {
+            if self.exclude.contains(&a) {
+                return Err(ConstraintError::Contradiction(a));
+            }
+            if !self.allows(ir[a].borrow()) {
+                return Err(ConstraintError::Unpaired(a));
+            }
+            for &b in &include[i + 1..] {
+                if conflict(ir[a].borrow(), ir[b].borrow()).is_some() {
+                    return Err(ConstraintError::Incoherent(a, b));
+                }
+            }
+        }
+        Ok(include)
+    }
+}
+
+/// Reasons why constraints can't be satisfied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConstraintError {
+    /// Repeat is both included and excluded
+    Contradiction(usize),
+    /// Included repeat pairs nucleotides in an unpaired range
+    Unpaired(usize),
+    /// Included repeats overlap or cross each other
+    Incoherent(usize, usize),
+    /// Included repeat is too long or has a too short loop
+    Limited(usize),
+    /// Included repeats are nested deeper than the maximum depth
+    Depth,
+}
+
+impl Display for ConstraintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintError::Contradiction(x) => write!(f, "Repeat {x} is both included and excluded"),
+            ConstraintError::Unpaired(x) => write!(f, "Included repeat {x} pairs nucleotides in an unpaired range"),
+            ConstraintError::Incoherent(a, b) => write!(f, "Included repeats {a} and {b} are not coherent"),
+            ConstraintError::Limited(x) => write!(f, "Included repeat {x} violates structural limits"),
+            ConstraintError::Depth => write!(f, "Included repeats exceed the maximum nesting depth"),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintError {}
@@ -1,13 +1,11 @@
 use std::borrow::Borrow;
-use std::collections::BTreeMap;
 use std::ops::Range;
 
 use itertools::Itertools;
-use num::traits::PrimInt;
 
-use super::{index, trace};
+use super::{index, score};
 use super::inv;
-use super::trace::TraceCell;
+use super::table::Table;
 
 // General rules:
 // 1. If a hypothesis is included -> the best subset of its embedded hypothesis is also included
@@ -19,30 +17,88 @@ use super::trace::TraceCell;
 // * f(s, e - 1)
 // * max of the following:
 //   * f(s, start(RNA)) + weight(RNA) + sum[f(start(gap_i), end(gap_i)) for all gaps in RNA where end(RNA) == e]
-struct Workload<'a, Idx, IR, Score>
+pub struct Workload<Idx, Score>
     where
         Idx: inv::Coordinate,
-        IR: Borrow<inv::Repeat<Idx>>,
-        Score: PrimInt
+        Score: Copy
 {
     pub index: index::Index<Idx>,
-    pub invrep: &'a [IR],
-    pub scores: &'a [Score],
+    pub scores: Vec<Score>,
+    // RNA id -> cells (sind, eind) with the best combinations inside each RNA gap
+    pub gaps: Vec<Vec<(usize, usize)>>,
+    // RNA id -> the closest end that doesn't contain the RNA
+    pub pre: Vec<Option<usize>>,
 }
 
-pub struct DynProgSolution<Score: PrimInt> {
-    pub tracer: trace::Tracer,
-    pub cache: Vec<BTreeMap<usize, Score>>,
+impl<Idx: inv::Coordinate, Score: Copy> Workload<Idx, Score> {
+    pub fn new(index: index::Index<Idx>, scores: &[Score]) -> Self {
+        let (gaps, pre) = (0..scores.len())
+            .map(|rnaid| {
+                let (rnasind, rnaeind) = index.revmap(rnaid);
+                let blocks = index.blocks(rnaid);
+                let pre = index::bisect::right(index.ends(), blocks[0].start, 0, rnaeind).checked_sub(1);
+                (gaps(&index, blocks, rnasind, rnaeind), pre)
+            })
+            .unzip();
+        Self { index, scores: scores.to_vec(), gaps, pre }
+    }
+
+    // Stored (first, last) ends for each row (start anchor), None if the row is not required.
+    // Rows are solved from right to left, and ends in each row are solved from left to right:
+    // * gaps of RNAs in the row always start at the later anchors
+    // * f(s, e) depends on f(s, e') for e' < e
+    pub fn extents(&self) -> Vec<Option<(usize, usize)>> {
+        let (starts, ends) = (self.index.starts().len(), self.index.ends().len());
+
+        // The last end required in each row
+        let mut required = vec![None; starts];
+        required[0] = Some(ends - 1);
+        for &(sind, eind) in self.gaps.iter().flatten() {
+            required[sind] = required[sind].max(Some(eind));
+        }
+
+        // Each row starts with the first end located after the row start
+        required.iter().enumerate().map(|(sind, required)| {
+            required.map(|x| (index::bisect::right(self.index.ends(), self.index.starts()[sind].pos, 0, ends), x))
+        }).collect()
+    }
 }
 
-impl<Score: PrimInt> DynProgSolution<Score> {
+#[derive(Clone, Copy)]
+pub struct Cell<Score: score::Score> {
+    pub score: Score,
+    // The best RNA ending at the cell, None if the cell is a copy of the previous end
+    pub rnaid: Option<u32>,
+}
+
+// Cells are stored in a dense banded table instead of per-row maps, and traces are recomputed from the chosen RNA
+// instead of being stored for each cell. See docs/src/algo/matching.md for the speedup on synthetic inputs.
+pub struct DynProgSolution<Score: score::Score> {
+    pub table: Table<Cell<Score>>,
+    // Solutions with the nesting depth <= i for each level i < max depth. Gaps of RNAs at each level are
+    // solved at the previous one. Empty if the depth is not limited, i.e. gaps are solved in the same table.
+    nested: Vec<Table<Cell<Score>>>,
+    // Maximum nesting depth of the solution, top-level RNAs have depth 1
+    depth: Option<usize>,
+    // Stored (first, last) ends for each row of the tables
+    extents: Vec<Option<(usize, usize)>>,
+}
+
+impl<Score: score::Score> DynProgSolution<Score> {
     pub fn new() -> Self {
         Self {
-            tracer: trace::Tracer::new(0, 0),
-            cache: vec![],
+            table: Table::new(Cell { score: Score::zero(), rnaid: None }),
+            nested: Vec::new(),
+            depth: None,
+            extents: Vec::new(),
         }
     }
 
+    pub fn with_depth(depth: usize) -> Self {
+        assert!(depth > 0, "Nesting depth must be positive");
+        Self { depth: Some(depth), ..Self::new() }
+    }
+
     pub fn solve<Idx, T>(
         &mut self, invrep: &[T], scores: &[Score],
     ) -> (Vec<usize>, Score)
@@ -51,193 +107,287 @@ impl<Score: PrimInt> DynProgSolution<Score> {
             T: Borrow<inv::Repeat<Idx>>
     {
         debug_assert!(invrep.len() == scores.len());
-        let w = Workload {
-            index: index::Index::new(invrep),
-            invrep: invrep,
-            scores,
-        };
+        self.solve_index(index::Index::new(invrep), scores)
+    }
+
+    // Units of the index are not limited to inverted repeats, e.g. they might be pseudoknots
+    pub fn solve_index<Idx: inv::Coordinate>(&mut self, index: index::Index<Idx>, scores: &[Score]) -> (Vec<usize>, Score) {
+        assert!(scores.len() < u32::MAX as usize, "Too many repeats to optimize");
+
+        let w = Workload::new(index, scores);
+        self.build(&w);
+        self.optimum(&w)
+    }
+
+    pub fn build<Idx: inv::Coordinate>(&mut self, w: &Workload<Idx, Score>) {
+        self.extents = w.extents();
 
-        // Prepare caches & the tracer
-        self.cache
-            .resize(w.index.starts().len(), Default::default());
-        self.tracer
-            .reset(w.index.starts().len(), w.index.ends().len());
+        self.nested.clear();
+        // Each level costs a full table, while limits at or above the deepest chain of nested RNAs never bind
+        if let Some(depth) = self.depth.filter(|x| *x < nesting(&w.index, w.scores.len())) {
+            // Nothing can be nested at the zero depth
+            let mut empty = Table::new(Cell { score: Score::zero(), rnaid: None });
+            empty.reset(self.extents.iter().map(|_| None));
+            self.nested.push(empty);
 
-        // Solve in a limited fashion to reduce the total number of recursive calls
-        for i in 0..w.index.ends().len() {
-            self.subsolve(&w, 0, i);
+            for _ in 1..depth {
+                let table = Self::fill(w, &self.extents, self.nested.last());
+                self.nested.push(table);
+            }
         }
-        let score = self.subsolve(&w, 0, w.index.ends().len() - 1);
-        let optimum = self
-            .tracer
-            .trace(0, w.index.ends().len() - 1);
-        (optimum, score)
+        self.table = Self::fill(w, &self.extents, self.nested.last());
     }
 
-    fn subsolve<Idx, IR>(&mut self, w: &Workload<Idx, IR, Score>, sind: usize, eind: usize) -> Score
-        where
-            Idx: inv::Coordinate,
-            IR: Borrow<inv::Repeat<Idx>>
-    {
-        // Sanity check
-        debug_assert!(sind <= w.index.starts().len() && eind <= w.index.ends().len());
+    // Recompute cells that contain the RNA after its score was changed in the workload. Other cells don't depend on
+    // it: each cell depends only on cells located inside it.
+    pub fn update<Idx: inv::Coordinate>(&mut self, w: &Workload<Idx, Score>, rnaid: usize) {
+        assert!(self.depth.is_none(), "Depth-limited solutions can't be updated");
 
-        // Prevent recursion for incorrect intervals
-        if w.index.starts()[sind].pos >= w.index.ends()[eind].pos {
-            return Score::zero();
+        let (rnasind, rnaeind) = w.index.revmap(rnaid);
+        for sind in (0..=rnasind).rev() {
+            if let Some((first, last)) = self.extents[sind] {
+                for eind in first.max(rnaeind)..=last {
+                    let cell = Self::subsolve(w, &self.table, &self.table, sind, eind);
+                    self.table.set(sind, eind, cell);
+                }
+            }
         }
+    }
 
-        // Cached lookup
-        if let Some(score) = self.cache[sind].get(&eind) {
-            return *score;
+    pub fn optimum<Idx: inv::Coordinate>(&self, w: &Workload<Idx, Score>) -> (Vec<usize>, Score) {
+        let ends = w.index.ends().len();
+        let score = self.table.get(0, ends - 1).score;
+        (self.trace(w, 0, ends - 1), score)
+    }
+
+    // Bottom-up order without recursion
+    fn fill<Idx>(
+        w: &Workload<Idx, Score>, extents: &[Option<(usize, usize)>], nested: Option<&Table<Cell<Score>>>,
+    ) -> Table<Cell<Score>>
+        where
+            Idx: inv::Coordinate
+    {
+        let mut table = Table::new(Cell { score: Score::zero(), rnaid: None });
+        table.reset(extents.iter().copied());
+        for (sind, extent) in extents.iter().enumerate().rev() {
+            if let Some((first, last)) = *extent {
+                for eind in first..=last {
+                    let cell = Self::subsolve(w, &table, nested.unwrap_or(&table), sind, eind);
+                    table.set(sind, eind, cell);
+                }
+            }
         }
+        table
+    }
 
-        let mut bestt = None;
+    fn subsolve<Idx>(
+        w: &Workload<Idx, Score>, table: &Table<Cell<Score>>, nested: &Table<Cell<Score>>, sind: usize, eind: usize,
+    ) -> Cell<Score>
+        where
+            Idx: inv::Coordinate
+    {
+        // Sanity check
+        debug_assert!(sind < w.index.starts().len() && eind < w.index.ends().len());
+        debug_assert!(w.index.starts()[sind].pos < w.index.ends()[eind].pos);
 
         // DP equation
         // * We skip the current end <- the best option is to use the previous end
+        let mut best = Cell { score: Score::zero(), rnaid: None };
         if eind > 0 {
-            let score = self.subsolve(w, sind, eind - 1);
-            if score > Score::zero() {
-                bestt = Some((
-                    trace::TraceCell {
-                        rnaid: None,
-                        included: vec![(sind, eind - 1)],
-                    },
-                    score,
-                ))
-            }
+            best.score = table.get(sind, eind - 1).score;
         }
 
         // * We look for the best rnafold here
-        // TODO: please borrow checker without doing clone here
-        for &rnaid in &w.index.ends()[eind].repeats.clone() {
-            let (rnasind, rnaeind) = w.index.revmap(rnaid);
+        for &rnaid in &w.index.ends()[eind].repeats {
             // Skip rnas that are not inside the current region
-            if rnasind < sind {
+            if w.index.revmap(rnaid).0 < sind {
                 continue;
             }
 
-            let rna = &w.invrep[rnaid];
-            let mut score = w.scores[rnaid];
-
-            debug_assert!(
-                rnasind >= sind
-                    && rnaeind == eind
-                    && w.index.starts()[sind].pos <= rna.borrow().brange().start
-                    && w.index.ends()[eind].pos >= rna.borrow().brange().end
-            );
-
-            // Include the best combination of 'embeddable' RNAs
-            let (gscore, mut trace) = self.gapsolve(w, w.index.blocks(rnaid), rnasind, rnaeind);
-            score = score + gscore;
-
-            // Find the closest end that doesn't contain the current rnafold
-            let mut preeind = index::bisect::right(w.index.ends(), rna.borrow().brange().start, 0, eind);
-            if preeind != 0 {
-                preeind -= 1;
-                debug_assert!(rna.borrow().brange().start >= w.index.ends()[preeind].pos);
-
-                // Can we include it?
-                if w.index.ends()[preeind].pos > w.index.starts()[sind].pos {
-                    let pscore = self.subsolve(w, sind, preeind);
-                    if pscore > Score::zero() {
-                        score = score + pscore;
-                        trace.push((sind, preeind));
-                    }
-                }
+            let score = Self::include(w, table, nested, sind, rnaid, |_| {});
+            if score.exceeds(Score::zero()) && score.exceeds(best.score) {
+                best = Cell { score, rnaid: Some(rnaid as u32) };
             }
+        }
 
-            if score > Score::zero() && (bestt.is_none() || bestt.as_ref().unwrap().1 < score) {
-                bestt = Some((
-                    TraceCell {
-                        rnaid: Some(rnaid),
-                        included: trace,
-                    },
-                    score,
-                ));
-            }
+        if !best.score.exceeds(Score::zero()) {
+            best.score = Score::zero();
         }
+        best
+    }
+
+    // Score of the RNA together with the best combination of RNAs in its gaps (from the nested table) and before it.
+    // Cells that contribute to the score are reported to the callback.
+    fn include<Idx>(
+        w: &Workload<Idx, Score>, table: &Table<Cell<Score>>, nested: &Table<Cell<Score>>,
+        sind: usize, rnaid: usize, mut included: impl FnMut((usize, usize)),
+    ) -> Score
+        where
+            Idx: inv::Coordinate
+    {
+        let mut score = w.scores[rnaid];
 
-        return match bestt {
-            None => {
-                // No traces scored > 0
-                self.cache[sind].insert(eind, Score::zero());
-                Score::zero()
+        // Include the best combination of 'embeddable' RNAs
+        for &(gsind, geind) in &w.gaps[rnaid] {
+            debug_assert!(gsind > sind);
+            let addition = nested.get(gsind, geind).score;
+            if addition.exceeds(Score::zero()) {
+                score = score.add(addition);
+                included((gsind, geind));
             }
-            Some(bestt) => {
-                self.tracer.update(sind, eind, bestt.0);
-                self.cache[sind].insert(eind, bestt.1);
-                bestt.1
+        }
+
+        // Can we include the closest end that doesn't contain the current rnafold?
+        if let Some(preeind) = w.pre[rnaid] {
+            if w.index.ends()[preeind].pos > w.index.starts()[sind].pos {
+                let pscore = table.get(sind, preeind).score;
+                if pscore.exceeds(Score::zero()) {
+                    score = score.add(pscore);
+                    included((sind, preeind));
+                }
             }
-        };
+        }
+        score
     }
 
-    fn gapsolve<Idx, IR>(
-        &mut self,
-        w: &Workload<Idx, IR, Score>,
-        blocks: &[Range<Idx>],
-        mut minsind: usize,
-        maxeind: usize,
-    ) -> (Score, Vec<(usize, usize)>)
+    // Table of the given level and the table with solutions for RNA gaps at this level
+    fn level(&self, level: usize) -> (&Table<Cell<Score>>, &Table<Cell<Score>>) {
+        match self.nested.len() {
+            0 => (&self.table, &self.table),
+            x if level == x => (&self.table, &self.nested[level - 1]),
+            _ => (&self.nested[level], &self.nested[level - 1]),
+        }
+    }
+
+    fn trace<Idx>(&self, w: &Workload<Idx, Score>, sind: usize, eind: usize) -> Vec<usize>
         where
-            Idx: inv::Coordinate,
-            IR: Borrow<inv::Repeat<Idx>>
+            Idx: inv::Coordinate
     {
-        // Blocks are sorted and for each start-end gap between adjacent blocks we need to find
-        // the best possible sind/eind so that: minsind < start(sind) <= start < end <= end(eind) < maxend
+        let mut rnaids = Vec::new();
 
-        let mut traces = Vec::new();
-        let (mut score, mut minend) = (Score::zero(), 0);
-
-        for (prv, nxt) in blocks.iter().tuple_windows() {
-            debug_assert!(prv.end <= nxt.start);
-
-            // No gap
-            if prv.end == nxt.start {
+        let mut queue = vec![(self.nested.len(), sind, eind)];
+        while let Some((level, sind, eind)) = queue.pop() {
+            let (table, nested) = self.level(level);
+            let cell = table.get(sind, eind);
+            if !cell.score.exceeds(Score::zero()) {
                 continue;
             }
-            let gap = Range {
-                start: prv.end,
-                end: nxt.start,
-            };
-
-            // Find the closest known interval inside the gap
-            let sind =
-                index::bisect::left(w.index.starts(), gap.start, minsind, w.index.starts().len());
-            let mut eind = index::bisect::right(w.index.ends(), gap.end, minend, maxeind);
-            debug_assert!(eind < w.index.ends().len() && sind <= w.index.starts().len());
-
-            // No rnas inside the gap
-            if eind == 0 || sind == w.index.starts().len() {
-                continue;
+
+            match cell.rnaid {
+                None => queue.push((level, sind, eind - 1)),
+                Some(rnaid) => {
+                    rnaids.push(rnaid as usize);
+                    // Gaps always start after the row start, unlike the preceding cell
+                    let sublevel = if self.nested.is_empty() { level } else { level - 1 };
+                    Self::include(w, table, nested, sind, rnaid as usize, |(s, e)| {
+                        queue.push((if s == sind { level } else { sublevel }, s, e))
+                    });
+                }
             }
-            eind -= 1;
+        }
+        rnaids
+    }
+}
 
-            let (start, end) = (w.index.starts()[sind].pos, w.index.ends()[eind].pos);
-            debug_assert!(gap.start <= start && end <= gap.end);
+// The longest chain of RNAs nested in each other's gaps, i.e. the maximum nesting depth of any solution.
+// Gaps are swept by their end: RNAs ending before the gap end are stored in a Fenwick tree over reversed start
+// anchors, so the deepest RNA starting inside the gap is a prefix maximum. Runs in O((RNAs + gaps) * log(starts)).
+fn nesting<Idx: inv::Coordinate>(index: &index::Index<Idx>, rnas: usize) -> usize {
+    let starts = index.starts().len();
+    let mut tree = vec![0; starts + 1];
 
-            // No valid matches
-            if start >= end {
-                continue;
+    // Gap end, first start anchor inside the gap and the RNA
+    let mut gaps = Vec::new();
+    for rnaid in 0..rnas {
+        for (prv, nxt) in index.blocks(rnaid).iter().tuple_windows() {
+            let sind = index::bisect::left(index.starts(), prv.end, 0, starts);
+            if sind < starts && index.starts()[sind].pos < nxt.start {
+                gaps.push((nxt.start, sind, rnaid));
             }
+        }
+    }
+    gaps.sort_by_key(|x| x.0);
 
-            // Find the best combination
-            let addition = self.subsolve(w, sind, eind);
-            if addition > Score::zero() {
-                score = score + addition;
-                traces.push((sind, eind));
-            }
+    // Gaps of each RNA end before the RNA itself => its height is known once all preceding gaps are processed
+    let mut pending = (0..rnas).sorted_by_key(|x| index.revmap(*x).1).peekable();
+    let mut nested = vec![0; rnas];
+    let mut deepest = 0;
+    for (end, sind, rnaid) in gaps {
+        while let Some(x) = pending.next_if(|x| index.ends()[index.revmap(*x).1].pos <= end) {
+            deepest = deepest.max(nested[x] + 1);
+            raise(&mut tree, starts - index.revmap(x).0, nested[x] + 1);
+        }
+        nested[rnaid] = nested[rnaid].max(prefix(&tree, starts - sind));
+    }
+    pending.map(|x| nested[x] + 1).fold(deepest, usize::max)
+}
+
+// Fenwick tree of maximums with 1-based positions
+fn raise(tree: &mut [usize], mut position: usize, value: usize) {
+    while position < tree.len() {
+        tree[position] = tree[position].max(value);
+        position += position & position.wrapping_neg();
+    }
+}
+
+fn prefix(tree: &[usize], mut position: usize) -> usize {
+    let mut result = 0;
+    while position > 0 {
+        result = result.max(tree[position]);
+        position -= position & position.wrapping_neg();
+    }
+    result
+}
 
-            // Since the gaps will only move right, we can be sure that:
-            // * next gaps will end after the current one
-            // * next gaps will start after the current one
-            minsind = sind;
-            minend = eind;
+// Blocks are sorted and for each start-end gap between adjacent blocks we need to find
+// the best possible sind/eind so that: minsind < start(sind) <= start < end <= end(eind) < maxend
+fn gaps<Idx: inv::Coordinate>(
+    index: &index::Index<Idx>,
+    blocks: &[Range<Idx>],
+    mut minsind: usize,
+    maxeind: usize,
+) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    let mut minend = 0;
+
+    for (prv, nxt) in blocks.iter().tuple_windows() {
+        debug_assert!(prv.end <= nxt.start);
+
+        // No gap
+        if prv.end == nxt.start {
+            continue;
+        }
+        let gap = Range {
+            start: prv.end,
+            end: nxt.start,
+        };
+
+        // Find the closest known interval inside the gap
+        let sind =
+            index::bisect::left(index.starts(), gap.start, minsind, index.starts().len());
+        let mut eind = index::bisect::right(index.ends(), gap.end, minend, maxeind);
+        debug_assert!(eind < index.ends().len() && sind <= index.starts().len());
+
+        // No rnas inside the gap
+        if eind == 0 || sind == index.starts().len() {
+            continue;
         }
-        debug_assert!(
-            score.is_zero() && traces.len() == 0 || score > Score::zero() && traces.len() > 0
-        );
-        (score, traces)
+        eind -= 1;
+
+        let (start, end) = (index.starts()[sind].pos, index.ends()[eind].pos);
+        debug_assert!(gap.start <= start && end <= gap.end);
+
+        // No valid matches
+        if start >= end {
+            continue;
+        }
+        cells.push((sind, eind));
+
+        // Since the gaps will only move right, we can be sure that:
+        // * next gaps will end after the current one
+        // * next gaps will start after the current one
+        minsind = sind;
+        minend = eind;
     }
+    cells
 }
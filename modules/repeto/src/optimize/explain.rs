@@ -0,0 +1,52 @@
+use std::borrow::Borrow;
+
+use derive_getters::{Dissolve, Getters};
+
+use super::conflict::{conflict, Conflict};
+use super::{inv, score, suboptimal};
+
+/// Why a repeat is (not) a part of the optimal solution.
+#[derive(Clone, Debug, PartialEq, Getters, Dissolve)]
+pub struct Explanation<Score: score::Score> {
+    // Selected repeats that share nucleotides with the repeat
+    overlaps: Vec<usize>,
+    // Selected repeats that cross the repeat
+    crosses: Vec<usize>,
+    // Total score of the solution
+    optimum: Score,
+    // Total score of the best solution that includes the repeat
+    forced: Score,
+}
+
+impl<Score: score::Score> Explanation<Score> {
+    /// Score lost by forcing the repeat into the solution.
+    pub fn delta(&self) -> Score { self.optimum.sub(self.forced) }
+}
+
+pub fn explain<Idx, IR, Score>(ir: &[IR], scores: &[Score], solution: &[usize], rnaid: usize) -> Explanation<Score>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let (mut overlaps, mut crosses) = (Vec::new(), Vec::new());
+    for &x in solution {
+        match conflict(ir[rnaid].borrow(), ir[x].borrow()) {
+            _ if x == rnaid => {}
+            Some(Conflict::Overlap) => overlaps.push(x),
+            Some(Conflict::Cross) => crosses.push(x),
+            None => {}
+        }
+    }
+    overlaps.sort();
+    crosses.sort();
+
+    let mut optimum = Score::zero();
+    for &x in solution {
+        optimum = optimum.add(scores[x]);
+    }
+
+    // A single repeat is always coherent
+    let (_, forced) = suboptimal::solve(ir, scores, &[rnaid], &[]).unwrap();
+    Explanation { overlaps, crosses, optimum, forced }
+}
@@ -0,0 +1,33 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use itertools::Itertools;
+
+use super::{inv, score};
+
+// Indices of repeats in each group, groups are ordered by their first appearance
+pub fn split<Key: Eq + Hash + Clone>(groups: &[Key]) -> Vec<(Key, Vec<usize>)> {
+    let mut order: HashMap<&Key, usize> = HashMap::new();
+    let mut result: Vec<(Key, Vec<usize>)> = Vec::new();
+    for (rnaid, key) in groups.iter().enumerate() {
+        let ind = *order.entry(key).or_insert_with(|| {
+            result.push((key.clone(), Vec::new()));
+            result.len() - 1
+        });
+        result[ind].1.push(rnaid);
+    }
+    result
+}
+
+pub fn solve<Idx, IR, Score>(ir: &[IR], scores: &[Score], group: &[usize]) -> (Vec<usize>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let subset = group.iter().map(|x| ir[*x].borrow()).collect_vec();
+    let subscores = group.iter().map(|x| scores[*x]).collect_vec();
+    let (solution, score) = super::run(&subset, &subscores);
+    (solution.into_iter().map(|x| group[x]).collect(), score)
+}
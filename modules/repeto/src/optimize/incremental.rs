@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashMap};
+
+use itertools::Itertools;
+
+use super::{index, inv, score};
+use super::dynprog::{DynProgSolution, Workload};
+
+/// Stateful optimizer that re-solves only the parts of the problem affected by changes.
+///
+/// Repeats are grouped into independent clusters with overlapping bounding ranges. Updating or removing a repeat
+/// recomputes only the DP cells that contain it. Adding a repeat merges all clusters it overlaps, and the merged
+/// cluster is rebuilt from scratch by the next `solve`, i.e. it costs O(merged cluster) while other clusters are kept
+/// intact. Removed repeats keep their ids and are never selected.
+pub struct Optimizer<Idx: inv::Coordinate, Score: score::Score> {
+    repeats: Vec<inv::Repeat<Idx>>,
+    scores: Vec<Score>,
+    removed: Vec<bool>,
+    // Clusters keyed by the start of their bounding range
+    clusters: BTreeMap<Idx, Cluster<Idx, Score>>,
+}
+
+struct Cluster<Idx: inv::Coordinate, Score: score::Score> {
+    end: Idx,
+    // Repeats that were not removed
+    rnaids: Vec<usize>,
+    // DP for the current set of repeats, None if it must be built from scratch
+    cache: Option<Cache<Idx, Score>>,
+    // Optimal solution, None if the cluster was changed since
+    solution: Option<(Vec<usize>, Score)>,
+}
+
+struct Cache<Idx: inv::Coordinate, Score: score::Score> {
+    // Position in the DP -> repeat id, removed repeats stay in the DP with zero scores
+    rnaids: Vec<usize>,
+    // Repeat id -> position in the DP
+    local: HashMap<usize, usize>,
+    w: Workload<Idx, Score>,
+    dp: DynProgSolution<Score>,
+}
+
+impl<Idx: inv::Coordinate, Score: score::Score> Default for Optimizer<Idx, Score> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<Idx: inv::Coordinate, Score: score::Score> Optimizer<Idx, Score> {
+    pub fn new() -> Self {
+        Self { repeats: Vec::new(), scores: Vec::new(), removed: Vec::new(), clusters: BTreeMap::new() }
+    }
+
+    /// Add the repeat and return its id, ids are assigned sequentially starting from 0.
+    ///
+    /// Clusters overlapping the repeat are merged and their DP is rebuilt by the next `solve`.
+    pub fn add(&mut self, repeat: inv::Repeat<Idx>, score: Score) -> usize {
+        assert!(score.is_valid(), "Scores must not be NaN");
+
+        let rnaid = self.repeats.len();
+        let brange = repeat.brange();
+        self.repeats.push(repeat);
+        self.scores.push(score);
+        self.removed.push(false);
+
+        // Clusters are disjoint => only the last one that starts before the repeat might overlap its start
+        let mut keys = self.clusters.range(..brange.start).next_back()
+            .filter(|(_, x)| x.end > brange.start)
+            .map(|(k, _)| *k)
+            .into_iter()
+            .collect_vec();
+        keys.extend(self.clusters.range(brange.start..brange.end).map(|(k, _)| *k));
+
+        let mut merged = Cluster { end: brange.end, rnaids: vec![rnaid], cache: None, solution: None };
+        let mut start = brange.start;
+        for key in keys {
+            let cluster = self.clusters.remove(&key).unwrap();
+            start = start.min(key);
+            merged.end = merged.end.max(cluster.end);
+            merged.rnaids.extend(cluster.rnaids);
+        }
+        // Same order as in `run` => ties are resolved in the same way
+        merged.rnaids.sort();
+        self.clusters.insert(start, merged);
+        rnaid
+    }
+
+    /// Change the score of the repeat.
+    pub fn update(&mut self, rnaid: usize, score: Score) {
+        assert!(!self.removed[rnaid], "Repeat {rnaid} was removed");
+        assert!(score.is_valid(), "Scores must not be NaN");
+        self.scores[rnaid] = score;
+        self.invalidate(rnaid, score);
+    }
+
+    /// Remove the repeat from the optimization.
+    pub fn remove(&mut self, rnaid: usize) {
+        assert!(!self.removed[rnaid], "Repeat {rnaid} was removed");
+        self.removed[rnaid] = true;
+        // Repeats with zero score are never selected, i.e. the cached DP stays valid
+        self.invalidate(rnaid, Score::zero());
+
+        let start = self.repeats[rnaid].brange().start;
+        let key = *self.clusters.range(..=start).next_back().unwrap().0;
+        let mut cluster = self.clusters.remove(&key).unwrap();
+        cluster.rnaids.retain(|x| *x != rnaid);
+        if cluster.rnaids.is_empty() {
+            return;
+        }
+
+        // Bounds of the cluster can only shrink
+        let start = cluster.rnaids.iter().map(|x| self.repeats[*x].brange().start).min().unwrap();
+        cluster.end = cluster.rnaids.iter().map(|x| self.repeats[*x].brange().end).max().unwrap();
+        self.clusters.insert(start, cluster);
+    }
+
+    /// Optimal coherent set of repeats, same as `run` for all repeats that were not removed.
+    pub fn solve(&mut self) -> (Vec<usize>, Score) {
+        let mut solution = Vec::new();
+        let mut score = Score::zero();
+        for cluster in self.clusters.values_mut() {
+            if cluster.solution.is_none() {
+                let cache = cluster.cache.get_or_insert_with(|| {
+                    let subset = cluster.rnaids.iter().map(|x| &self.repeats[*x]).collect_vec();
+                    let subscores = cluster.rnaids.iter().map(|x| self.scores[*x]).collect_vec();
+
+                    let w = Workload::new(index::Index::new(&subset), &subscores);
+                    let mut dp = DynProgSolution::new();
+                    dp.build(&w);
+                    let local = cluster.rnaids.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+                    Cache { rnaids: cluster.rnaids.clone(), local, w, dp }
+                });
+
+                let (selected, score) = cache.dp.optimum(&cache.w);
+                cluster.solution = Some((selected.into_iter().map(|x| cache.rnaids[x]).collect(), score));
+            }
+
+            let (selected, subscore) = cluster.solution.as_ref().unwrap();
+            solution.extend(selected.iter().copied());
+            score = score.add(*subscore);
+        }
+        solution.sort();
+        (solution, score)
+    }
+
+    fn invalidate(&mut self, rnaid: usize, score: Score) {
+        let start = self.repeats[rnaid].brange().start;
+        let (_, cluster) = self.clusters.range_mut(..=start).next_back().unwrap();
+        cluster.solution = None;
+        if let Some(cache) = &mut cluster.cache {
+            let local = cache.local[&rnaid];
+            cache.w.scores[local] = score;
+            cache.dp.update(&cache.w, local);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(left: std::ops::Range<isize>, right: std::ops::Range<isize>) -> inv::Repeat<isize> {
+        inv::Repeat::new(vec![inv::Segment::new(left, right)])
+    }
+
+    fn cached(optimizer: &Optimizer<isize, i32>) -> Vec<(isize, Vec<usize>, bool)> {
+        optimizer.clusters.iter().map(|(k, x)| (*k, x.rnaids.clone(), x.cache.is_some())).collect()
+    }
+
+    #[test]
+    fn add() {
+        let mut optimizer = Optimizer::new();
+        optimizer.add(repeat(0..2, 8..10), 1);
+        optimizer.add(repeat(20..22, 28..30), 1);
+        optimizer.add(repeat(40..42, 48..50), 1);
+        optimizer.solve();
+        assert_eq!(cached(&optimizer), vec![(0, vec![0], true), (20, vec![1], true), (40, vec![2], true)]);
+
+        // Only the touched cluster is rebuilt
+        optimizer.add(repeat(21..23, 25..27), 2);
+        assert_eq!(cached(&optimizer), vec![(0, vec![0], true), (20, vec![1, 3], false), (40, vec![2], true)]);
+
+        // Bridging repeat merges clusters
+        optimizer.add(repeat(5..7, 41..43), 1);
+        assert_eq!(cached(&optimizer), vec![(0, vec![0, 1, 2, 3, 4], false)]);
+        assert_eq!(optimizer.solve(), (vec![0, 2, 3], 4));
+        assert!(cached(&optimizer)[0].2);
+    }
+
+    #[test]
+    fn remove() {
+        let mut optimizer = Optimizer::new();
+        optimizer.add(repeat(0..2, 8..10), 1);
+        optimizer.add(repeat(5..7, 20..22), 3);
+        optimizer.add(repeat(15..17, 30..32), 1);
+        assert_eq!(optimizer.solve(), (vec![1], 3));
+
+        // Removed repeats are dropped from clusters, the cached DP is kept
+        optimizer.remove(0);
+        assert_eq!(cached(&optimizer), vec![(5, vec![1, 2], true)]);
+        assert_eq!(optimizer.solve(), (vec![1], 3));
+
+        optimizer.remove(1);
+        assert_eq!(cached(&optimizer), vec![(15, vec![2], true)]);
+        assert_eq!(optimizer.solve(), (vec![2], 1));
+
+        optimizer.remove(2);
+        assert!(cached(&optimizer).is_empty());
+        assert_eq!(optimizer.solve(), (vec![], 0));
+
+        let rnaid = optimizer.add(repeat(3..5, 12..14), 2);
+        assert_eq!(optimizer.solve(), (vec![rnaid], 2));
+    }
+}
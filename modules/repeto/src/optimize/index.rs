@@ -28,20 +28,17 @@ impl<Idx: inv::Coordinate> Index<Idx> {
         where
             T: Borrow<inv::Repeat<Idx>>
     {
-        let (starts, revstart) = Index::index(invrep, |x| x.borrow().brange().start);
-        let (ends, revend) = Index::index(invrep, |x| x.borrow().brange().end);
+        Self::from_blocks(invrep.iter().map(|x| x.borrow().seqranges().cloned().collect()).collect())
+    }
 
-        let blocks = invrep
-            .iter()
-            .map(|x| {
-                let blocks: Vec<_> = x.borrow().seqranges().cloned().collect();
-                debug_assert!(
-                    blocks.iter().tuple_windows().all(|(prv, nxt)| prv.end <= nxt.start)
-                );
+    // Index arbitrary units represented by their sorted non-overlapping blocks
+    pub fn from_blocks(blocks: Vec<Vec<Range<Idx>>>) -> Self {
+        debug_assert!(blocks.iter().all(|x| {
+            !x.is_empty() && x.iter().tuple_windows().all(|(prv, nxt)| prv.end <= nxt.start)
+        }));
 
-                blocks
-            })
-            .collect();
+        let (starts, revstart) = Index::index(&blocks, |x| x[0].start);
+        let (ends, revend) = Index::index(&blocks, |x| x.last().unwrap().end);
 
         Self {
             starts,
@@ -68,7 +65,7 @@ impl<Idx: inv::Coordinate> Index<Idx> {
         &self.blocks[rnaid]
     }
 
-    fn index<T: Borrow<inv::Repeat<Idx>>>(
+    fn index<T>(
         rnas: &[T],
         key: impl for<'b> Fn(&'b T) -> Idx,
     ) -> (Vec<IndexAnchor<Idx>>, Vec<usize>) {
@@ -0,0 +1,32 @@
+use derive_getters::{Dissolve, Getters};
+
+use super::inv;
+
+/// Structural limits on the optimal solution.
+///
+/// The DP stores a separate table for each nesting level, i.e. memory grows linearly with `max_depth`. Limits at or
+/// above the deepest chain of nested repeats in a cluster don't bind and cost the same as the unlimited optimization.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Getters, Dissolve)]
+pub struct Limits<Idx: inv::Coordinate> {
+    // Maximum length of the bounding range of each selected repeat
+    max_span: Option<Idx>,
+    // Maximum nesting depth of the solution, top-level repeats have depth 1
+    max_depth: Option<usize>,
+    // Minimum unpaired loop between the innermost arms of each selected repeat
+    min_loop: Option<Idx>,
+}
+
+impl<Idx: inv::Coordinate> Limits<Idx> {
+    pub fn new(max_span: Option<Idx>, max_depth: Option<usize>, min_loop: Option<Idx>) -> Self {
+        assert!(max_depth != Some(0), "Maximum nesting depth must be positive");
+        Self { max_span, max_depth, min_loop }
+    }
+
+    // Anything nested in the innermost gap has an even smaller loop, i.e. repeats with short loops can be dropped
+    // before the optimization
+    pub(super) fn allows(&self, repeat: &inv::Repeat<Idx>) -> bool {
+        let brange = repeat.brange();
+        self.max_span.map_or(true, |x| brange.end - brange.start <= x)
+            && self.min_loop.map_or(true, |x| repeat.inner_gap() >= x)
+    }
+}
@@ -1,29 +1,276 @@
 use std::borrow::Borrow;
-
-use num::traits::PrimInt;
+use std::fmt::Debug;
+use std::hash::Hash;
 
 use super::repeats::inv;
 
+pub use approx::Strategy;
+pub use constraints::{ConstraintError, Constraints};
+pub use explain::Explanation;
+pub use incremental::Optimizer;
+pub use limits::Limits;
+pub use options::{Options, Solution};
+pub use partition::Ensemble;
+pub use score::Score;
+pub use ties::TieBreaking;
+pub use tree::TreeNode;
+pub use trim::Trimming;
+pub use weights::Weights;
+
+mod approx;
+mod components;
+mod conflict;
+mod constraints;
 mod dynprog;
+mod explain;
+mod groups;
+mod incremental;
 mod index;
-mod trace;
+mod limits;
+mod options;
+mod partition;
+mod pknot;
+mod sample;
+mod score;
+mod suboptimal;
+mod table;
+mod ties;
+mod tolerance;
+mod tree;
+mod trim;
+mod weights;
 
+/// Find a score-maximal coherent set of repeats.
+///
+/// Repeats with non-overlapping bounding ranges are optimized independently. Returns indices of the selected repeats
+/// in the ascending order and the total score. Ties between equally scored solutions are resolved deterministically,
+/// but the choice depends on the input order, see `Options` for a stable policy and other settings.
 pub fn run<Idx, IR, Score>(ir: &[IR], scores: &[Score]) -> (Vec<usize>, Score)
     where
         Idx: inv::Coordinate,
         IR: Borrow<inv::Repeat<Idx>>,
-        Score: PrimInt
+        Score: score::Score
 {
-    assert_eq!(ir.len(), scores.len());
+    validate(ir, scores);
 
-    // Trivial solutions
-    if ir.is_empty() || (ir.len() == 1 && scores[0].is_zero()) {
-        return (vec![], Score::zero());
-    } else if ir.len() == 1 && scores[0] > Score::zero() {
-        return (vec![0], scores[0]);
-    }
+    let results = components::clusters(ir, scores)
+        .into_iter()
+        .map(|x| components::solve(ir, scores, &x, None))
+        .collect();
+    components::merge(results)
+}
+
+/// Same as `run`, but independent clusters of repeats are optimized in parallel.
+#[cfg(feature = "parallel")]
+pub fn run_parallel<Idx, IR, Score>(ir: &[IR], scores: &[Score]) -> (Vec<usize>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>> + Sync,
+        Score: score::Score + Send + Sync
+{
+    use rayon::prelude::*;
+
+    validate(ir, scores);
+
+    let results = components::clusters(ir, scores)
+        .into_par_iter()
+        .map(|x| components::solve(ir, scores, &x, None))
+        .collect();
+    components::merge(results)
+}
+
+/// Optimize groups of repeats, e.g. from different contigs, strands or windows, independently of each other.
+///
+/// Returns groups in the order of their first appearance together with the selected repeats (indices in the input,
+/// in the ascending order) and the total score of each group.
+pub fn run_grouped<Idx, IR, Score, Key>(ir: &[IR], scores: &[Score], groups: &[Key]) -> Vec<(Key, Vec<usize>, Score)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score,
+        Key: Eq + Hash + Clone
+{
+    validate(ir, scores);
+    assert_eq!(ir.len(), groups.len());
+
+    groups::split(groups)
+        .into_iter()
+        .map(|(key, group)| {
+            let (solution, score) = groups::solve(ir, scores, &group);
+            (key, solution, score)
+        })
+        .collect()
+}
+
+/// Same as `run_grouped`, but groups are optimized in parallel.
+#[cfg(feature = "parallel")]
+pub fn run_grouped_parallel<Idx, IR, Score, Key>(
+    ir: &[IR], scores: &[Score], groups: &[Key],
+) -> Vec<(Key, Vec<usize>, Score)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>> + Sync,
+        Score: score::Score + Send + Sync,
+        Key: Eq + Hash + Clone + Send + Sync
+{
+    use rayon::prelude::*;
 
-    dynprog::DynProgSolution::new().solve(ir, scores)
+    validate(ir, scores);
+    assert_eq!(ir.len(), groups.len());
+
+    groups::split(groups)
+        .into_par_iter()
+        .map(|(key, group)| {
+            let (solution, score) = groups::solve(ir, scores, &group);
+            (key, solution, score)
+        })
+        .collect()
+}
+
+/// Find a score-maximal coherent set of repeats with the given options, e.g. constraints, limits or trimming.
+///
+/// Included repeats are always reported, even if their scores are not positive. Repeats that are too long or have
+/// too short loops are never selected, while the nesting depth is limited by the DP itself, i.e. deeper repeats are
+/// traded for the best shallower alternatives. Per-nucleotide weights are added to the given scores.
+///
+/// Trimmed repeats are scored proportionally to the number of retained base pairs, and a repeat might be reported as
+/// several pieces if other repeats are placed between them. With a positive tolerance, two repeats can be selected
+/// together if removing up to `tolerance` base pairs from the outer and inner ends of the lower-scoring one resolves
+/// their overlap. Only lower-scoring repeats are trimmed in the output, while their scores are kept intact.
+///
+/// Fails if included repeats are not coherent, pair nucleotides in unpaired ranges, are also excluded or violate
+/// the limits. Panics for unsupported combinations of options: trimming with the overlap tolerance or the nesting
+/// depth, and approximate strategies with included repeats or the nesting depth.
+pub fn run_with<Idx, IR, Score>(
+    ir: &[IR], scores: &[Score], options: &Options<Idx, Score>,
+) -> Result<Solution<Idx, Score>, ConstraintError>
+    where
+        Idx: inv::Coordinate + Debug,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    options::run(ir, scores, options)
+}
+
+/// Explain why the repeat is (not) a part of the solution returned by `run`.
+///
+/// Reports selected repeats that overlap or cross the given one, and the score of the best solution that includes it.
+pub fn explain<Idx, IR, Score>(ir: &[IR], scores: &[Score], solution: &[usize], rnaid: usize) -> Explanation<Score>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    assert!(rnaid < ir.len() && solution.iter().all(|x| *x < ir.len()), "Repeats must be valid indices");
+    explain::explain(ir, scores, solution, rnaid)
+}
+
+/// Boltzmann ensemble of all coherent repeat sets, where each set has the weight exp(total score / temperature).
+///
+/// Reports the partition function together with the marginal inclusion probability of each repeat and the pairing
+/// probability of each nucleotide. Unlike the optimization, non-positive repeats are also a part of the ensemble.
+pub fn partition<Idx, IR, Score>(ir: &[IR], scores: &[Score], temperature: f64) -> Ensemble<Idx>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    assert!(temperature > 0.0, "Temperature must be positive");
+    partition::partition(ir, scores, temperature)
+}
+
+/// Draw n coherent sets of repeats from the Boltzmann ensemble, where each set has the weight
+/// exp(total score / temperature), i.e. the same distribution as reported by `partition`.
+///
+/// Samples are reproducible for the same seed, and each sample is reported as sorted indices of the selected repeats.
+pub fn sample<Idx, IR, Score>(ir: &[IR], scores: &[Score], temperature: f64, n: usize, seed: u64) -> Vec<Vec<usize>>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    assert!(temperature > 0.0, "Temperature must be positive");
+    sample::sample(ir, scores, temperature, n, seed)
+}
+
+/// Find the k best distinct coherent sets of repeats, ordered by their total score (best first).
+///
+/// Only repeats with positive scores are considered, i.e. sets that differ only by non-positive repeats
+/// are not reported. The first reported set is the optimal one, as returned by `run`.
+pub fn top_k<Idx, IR, Score>(ir: &[IR], scores: &[Score], k: usize) -> Vec<(Vec<usize>, Score)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    suboptimal::enumerate(ir, scores, |reported, _| reported < k)
+}
+
+/// Find all distinct coherent sets of repeats with the total score >= optimum - delta, best first.
+///
+/// Note that the number of such sets can grow exponentially with the delta.
+pub fn within_delta<Idx, IR, Score>(ir: &[IR], scores: &[Score], delta: Score) -> Vec<(Vec<usize>, Score)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    assert!(delta.is_valid(), "Scores must not be NaN");
+    let mut optimum = None;
+    suboptimal::enumerate(ir, scores, |_, score| {
+        let optimum = *optimum.get_or_insert(score);
+        !optimum.exceeds(score.add(delta))
+    })
+}
+
+/// Find a score-maximal set of repeats, where pairs of crossing repeats may form H-type pseudoknots.
+///
+/// Unlike `run`, two repeats with left(a) < left(b) < right(a) < right(b) can be included together at the cost of
+/// the given penalty. All other repeats must be coherent with both of them, i.e. they can be placed inside
+/// pseudoknot loops but can't cross them. Returns selected repeats, crossing pairs among them and the total score.
+///
+/// Only repeats with overlapping bounding ranges are tested for crossing. In the worst case, e.g. when all repeats
+/// share a common position, this is still O(n^2) pairs and each H-type pair adds a unit to the optimization.
+pub fn run_pseudoknots<Idx, IR, Score>(
+    ir: &[IR], scores: &[Score], penalty: Score,
+) -> (Vec<usize>, Vec<(usize, usize)>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    validate(ir, scores);
+    assert!(penalty.is_valid(), "Scores must not be NaN");
+    pknot::run(ir, scores, penalty)
+}
+
+/// Arrange selected repeats, e.g. an optimal solution, into a tree where children are nested in gaps of their parents.
+///
+/// Top-level repeats and children of each node are ordered by their start position. Panics if selected repeats are
+/// not coherent.
+pub fn nest<Idx, IR>(ir: &[IR], selected: &[usize]) -> Vec<TreeNode>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>
+{
+    tree::nest(ir, selected)
+}
+
+// Sanity checks shared by all entry points
+fn validate<Idx, IR, Score>(ir: &[IR], scores: &[Score])
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    assert_eq!(ir.len(), scores.len());
+    assert!(scores.iter().all(|x| x.is_valid()), "Scores must not be NaN");
 }
 
 #[cfg(test)]
@@ -283,4 +530,830 @@ mod tests {
             dotest(w);
         }
     }
+
+    fn repeats(data: Vec<Vec<(Range<isize>, Range<isize>)>>) -> Vec<inv::Repeat<isize>> {
+        data.into_iter()
+            .map(|x| inv::Repeat::new(x.into_iter().map(|s| s.into()).collect()))
+            .collect()
+    }
+
+    // All coherent sets of positive repeats sorted by their score
+    fn bruteforce(ir: &[inv::Repeat<isize>], scores: &[Score]) -> Vec<Score> {
+        let positive = (0..ir.len()).filter(|x| scores[*x] > 0).collect_vec();
+        let mut results = Vec::new();
+        for subset in positive.into_iter().powerset() {
+            let coherent = subset.iter().tuple_combinations()
+                .all(|(a, b)| conflict::conflict(&ir[*a], &ir[*b]).is_none());
+            if coherent {
+                results.push(subset.iter().map(|x| scores[*x]).sum());
+            }
+        }
+        results.sort_by_key(|x| std::cmp::Reverse(*x));
+        results
+    }
+
+    #[test]
+    fn top_k() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 31..32), (24..25, 29..30)],
+            vec![(20..22, 30..32), (24..26, 27..29)],
+            vec![(40..45, 50..55)],
+        ]);
+        let scores = vec![3, 1, 2, 1, 4, 6, 0];
+
+        let expected = bruteforce(&ir, &scores);
+        let optimum = run(&ir, &scores);
+
+        for k in [0, 1, 5, expected.len(), expected.len() + 10] {
+            let result = super::top_k(&ir, &scores, k);
+            assert_eq!(result.len(), k.min(expected.len()));
+            assert_eq!(result.iter().map(|x| x.1).collect_vec(), expected[..result.len()]);
+
+            // All reported sets are distinct
+            let unique = result.iter().map(|x| x.0.iter().sorted().collect_vec()).unique().count();
+            assert_eq!(unique, result.len());
+
+            if k > 0 {
+                assert_eq!(result[0].0.iter().sorted().collect_vec(), optimum.0.iter().sorted().collect_vec());
+            }
+        }
+
+        let result = within_delta(&ir, &scores, 2);
+        let expected = expected.into_iter().filter(|x| *x >= optimum.1 - 2).collect_vec();
+        assert_eq!(result.into_iter().map(|x| x.1).collect_vec(), expected);
+    }
+
+    #[test]
+    fn floats() {
+        let ir = repeats(vec![
+            vec![(0..4, 5..9)],
+            vec![(9..12, 15..19)],
+            vec![(1..5, 7..10)],
+            vec![(10..12, 17..20)],
+            vec![(5..9, 15..19)],
+            vec![(20..25, 30..35)],
+        ]);
+
+        let scores = vec![0.1f64, 0.2, 0.3, 0.4, 1.5, 1.0];
+        let (mut result, score) = run(&ir, &scores);
+        result.sort();
+        assert_eq!(result, vec![4, 5]);
+        assert!((score - 2.5).abs() < 1e-9);
+
+        // Tied solutions: 0.1 + 0.2 == 0.3 within the tolerance
+        let scores = vec![0.1f32, 0.2, 0.0, 0.0, 0.3, 0.0];
+        let (_, score) = run(&ir, &scores);
+        assert!((score - 0.3).abs() < 1e-6);
+
+        // Tiny positive scores are kept
+        let scores = vec![1e-7f64, 0.0, 0.0, 2e-7, 0.0, 1e-7];
+        let (mut result, score) = run(&ir, &scores);
+        result.sort();
+        assert_eq!(result, vec![0, 3, 5]);
+        assert!((score - 4e-7).abs() < 1e-15);
+
+        let scores = vec![1e-6f32, 0.0, 0.0, 2e-6, 0.0, 1e-6];
+        let (mut result, _) = run(&ir, &scores);
+        result.sort();
+        assert_eq!(result, vec![0, 3, 5]);
+    }
+
+    #[test]
+    fn lexicographic() {
+        let ir = repeats(vec![
+            vec![(0..4, 5..9)],
+            vec![(9..12, 15..19)],
+            vec![(1..5, 7..10)],
+            vec![(10..12, 17..20)],
+            vec![(5..9, 15..19)],
+            vec![(20..25, 30..35)],
+        ]);
+
+        // Ties in the primary objective are broken by the secondary one
+        let scores: Vec<(i32, f64)> = vec![(1, 0.5), (1, 0.5), (0, 2.0), (0, 0.0), (2, 1.5), (0, -1.0)];
+        assert_eq!(run(&ir, &scores), (vec![4], (2, 1.5)));
+
+        let scores: Vec<(i32, f64)> = vec![(1, 0.5), (1, 0.5), (0, 2.0), (0, 0.0), (2, 0.1), (0, -1.0)];
+        assert_eq!(run(&ir, &scores), (vec![0, 1], (2, 1.0)));
+
+        // Secondary objective never outweighs the primary one
+        let scores = vec![(1, 0, 0), (1, 0, 0), (0, 100, 0), (0, 100, 0), (3, 0, 1), (1, -5, 0)];
+        assert_eq!(run(&ir, &scores).1, (4, -5, 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan() {
+        let ir = repeats(vec![vec![(0..4, 5..9)], vec![(10..12, 17..20)]]);
+        run(&ir, &[1.0, f64::NAN]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn constrained() {
+        let ir = repeats(vec![
+            vec![(0..4, 5..9)],
+            vec![(9..12, 15..19)],
+            vec![(1..5, 7..10)],
+            vec![(10..12, 17..20)],
+            vec![(5..9, 15..19)],
+            vec![(20..25, 30..35)],
+        ]);
+        let scores = vec![1, 2, 3, 4, 15, -1];
+
+        let solve = |include, exclude, unpaired| {
+            let options = Options::new().with_constraints(Constraints::new(include, exclude, unpaired));
+            run_with(&ir, &scores, &options).map(|x| (x.selected().clone(), *x.score()))
+        };
+
+        assert_eq!(solve(vec![], vec![], vec![]), Ok((vec![4], 15)));
+        assert_eq!(solve(vec![0, 5], vec![], vec![]), Ok((vec![0, 3, 5], 4)));
+        assert_eq!(solve(vec![], vec![4], vec![]), Ok((vec![2, 3], 7)));
+        assert_eq!(solve(vec![], vec![], vec![8..9]), Ok((vec![3], 4)));
+        // Unpaired range inside the gap
+        assert_eq!(solve(vec![], vec![], vec![10..14]), Ok((vec![4], 15)));
+
+        assert_eq!(solve(vec![0, 2], vec![], vec![]), Err(ConstraintError::Incoherent(0, 2)));
+        assert_eq!(solve(vec![1], vec![1], vec![]), Err(ConstraintError::Contradiction(1)));
+        assert_eq!(solve(vec![4], vec![], vec![0..6]), Err(ConstraintError::Unpaired(4)));
+    }
+
+    #[test]
+    fn nesting() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 23..24)],
+            vec![(40..45, 50..55)],
+        ]);
+        let (solution, _) = run(&ir, &[1, 1, 1, 1, 1, 1]);
+        assert_eq!(solution.iter().copied().sorted().collect_vec(), (0..6).collect_vec());
+
+        let leaf = |rnaid, gap| TreeNode::new(rnaid, Some(gap), vec![]);
+        let expected = vec![
+            TreeNode::new(0, None, vec![
+                leaf(1, 0),
+                TreeNode::new(2, Some(1), vec![leaf(3, 0)]),
+                leaf(4, 1),
+            ]),
+            TreeNode::new(5, None, vec![]),
+        ];
+        assert_eq!(nest(&ir, &solution), expected);
+        assert_eq!(nest(&ir, &[]), vec![]);
+    }
+
+    #[test]
+    fn limited() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 23..24)],
+            vec![(40..45, 50..55)],
+            vec![(9..10, 21..22)],
+        ]);
+        let scores = vec![3, 1, 2, 1, 1, 2, 1];
+
+        let solve = |limits: &Limits<isize>| {
+            let options = Options::new().with_limits(limits.clone());
+            let (selected, _, score) = run_with(&ir, &scores, &options).unwrap().dissolve();
+            (selected, score)
+        };
+        fn depth(nodes: &[TreeNode]) -> usize {
+            nodes.iter().map(|x| 1 + depth(x.children())).max().unwrap_or(0)
+        }
+
+        for (max_span, max_depth, min_loop) in [
+            (None, None, None), (None, Some(1), None), (None, Some(2), None), (None, Some(3), None),
+            (None, Some(4), None), (None, Some(10), None),
+            (Some(20), None, None), (None, None, Some(1)), (Some(31), Some(2), Some(1)),
+        ] {
+            let limits = Limits::new(max_span, max_depth, min_loop);
+            let allowed = |x: &usize| {
+                let brange = ir[*x].brange();
+                max_span.map_or(true, |s| brange.end - brange.start <= s)
+                    && min_loop.map_or(true, |l| ir[*x].inner_gap() >= l)
+            };
+
+            let mut expected = 0;
+            for subset in (0..ir.len()).filter(allowed).powerset() {
+                let coherent = subset.iter().tuple_combinations()
+                    .all(|(a, b)| conflict::conflict(&ir[*a], &ir[*b]).is_none());
+                if coherent && max_depth.map_or(true, |d| depth(&nest(&ir, &subset)) <= d) {
+                    expected = expected.max(subset.iter().map(|x| scores[*x]).sum());
+                }
+            }
+
+            let (solution, score) = solve(&limits);
+            assert_eq!(score, expected, "{limits:?}");
+            assert_eq!(solution.iter().map(|x| scores[*x]).sum::<Score>(), score);
+            assert!(solution.iter().all(allowed));
+            assert!(max_depth.map_or(true, |d| depth(&nest(&ir, &solution)) <= d));
+        }
+        assert_eq!(solve(&Limits::default()), run(&ir, &scores));
+    }
+
+    #[test]
+    #[should_panic]
+    fn nesting_incoherent() {
+        let ir = repeats(vec![vec![(0..5, 20..25)], vec![(10..15, 30..35)]]);
+        nest(&ir, &[0, 1]);
+    }
+
+    #[test]
+    fn explanation() {
+        let ir = repeats(vec![
+            vec![(0..5, 20..25)],
+            vec![(10..15, 30..35)],
+            vec![(3..6, 7..10)],
+            vec![(16..17, 18..19)],
+            vec![(40..45, 50..55)],
+        ]);
+        let scores = vec![5, 6, 3, 1, -1];
+        let (solution, optimum) = run(&ir, &scores);
+        assert_eq!((solution.clone(), optimum), (vec![1, 2, 3], 10));
+
+        let explanation = explain(&ir, &scores, &solution, 0);
+        assert_eq!(explanation.overlaps(), &vec![2]);
+        assert_eq!(explanation.crosses(), &vec![1]);
+        assert_eq!((*explanation.optimum(), *explanation.forced(), explanation.delta()), (10, 6, 4));
+
+        let explanation = explain(&ir, &scores, &solution, 4);
+        assert!(explanation.overlaps().is_empty() && explanation.crosses().is_empty());
+        assert_eq!(explanation.delta(), 1);
+
+        // Selected repeats are explained by themselves
+        let explanation = explain(&ir, &scores, &solution, 1);
+        assert!(explanation.overlaps().is_empty() && explanation.crosses().is_empty());
+        assert_eq!(explanation.delta(), 0);
+    }
+
+    #[test]
+    fn ensemble() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 31..32), (24..25, 29..30)],
+            vec![(20..22, 30..32), (24..26, 27..29)],
+            vec![(1..8, 40..47)],
+            vec![(34..35, 37..38)],
+        ]);
+        let scores = vec![3, 1, 2, -1, 4, 6, 5, 0];
+        let temperature = 2.0;
+
+        // Enumerate all coherent sets
+        let (mut z, mut expected) = (0.0, vec![0.0; ir.len()]);
+        for subset in (0..ir.len()).powerset() {
+            let coherent = subset.iter().tuple_combinations()
+                .all(|(a, b)| conflict::conflict(&ir[*a], &ir[*b]).is_none());
+            if coherent {
+                let weight = (subset.iter().map(|x| scores[*x]).sum::<Score>() as f64 / temperature).exp();
+                z += weight;
+                for x in subset {
+                    expected[x] += weight;
+                }
+            }
+        }
+
+        let ensemble = partition(&ir, &scores, temperature);
+        assert!((ensemble.logz() - z.ln()).abs() < 1e-9);
+        for (probability, expected) in ensemble.probabilities().iter().zip(expected) {
+            assert!((probability - expected / z).abs() < 1e-9, "{probability} vs {}", expected / z);
+        }
+
+        assert_eq!(ensemble.range(), &(0..47));
+        assert_eq!(ensemble.pairing().len(), 47);
+        let (one, six) = (ensemble.probabilities()[1], ensemble.probabilities()[6]);
+        assert!((ensemble.pairing()[3] - one - six).abs() < 1e-9);
+        assert_eq!(ensemble.pairing()[4], ensemble.pairing()[3] - one);
+        assert_eq!(ensemble.pairing()[46], six);
+        assert_eq!(ensemble.pairing()[39], 0.0);
+
+        let empty = partition(&ir[..0], &scores[..0], 1.0);
+        assert_eq!((*empty.logz(), empty.pairing().len()), (0.0, 0));
+    }
+
+    #[test]
+    fn sampling() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 31..32), (24..25, 29..30)],
+            vec![(20..22, 30..32), (24..26, 27..29)],
+            vec![(1..8, 40..47)],
+        ]);
+        let scores = vec![1.5, 0.5, 1.0, -0.5, 2.0, 3.0, 2.5];
+
+        let samples = super::sample(&ir, &scores, 1.0, 20_000, 42);
+        assert_eq!(samples, super::sample(&ir, &scores, 1.0, 20_000, 42));
+        assert_ne!(samples, super::sample(&ir, &scores, 1.0, 20_000, 43));
+
+        for temperature in [1.0, 0.5, 3.0] {
+            let samples = super::sample(&ir, &scores, temperature, 20_000, 42);
+            let mut frequencies = vec![0.0; ir.len()];
+            for sample in &samples {
+                for (a, b) in sample.iter().tuple_combinations() {
+                    assert!(conflict::conflict(&ir[*a], &ir[*b]).is_none());
+                }
+                for x in sample {
+                    frequencies[*x] += 1.0 / samples.len() as f64;
+                }
+            }
+
+            let ensemble = partition(&ir, &scores, temperature);
+            for (frequency, probability) in frequencies.into_iter().zip(ensemble.probabilities()) {
+                assert!((frequency - probability).abs() < 0.02, "T={temperature}: {frequency} vs {probability}");
+            }
+        }
+
+        assert_eq!(super::sample(&ir[..0], &scores[..0], 1.0, 2, 0), vec![vec![], vec![]]);
+    }
+
+    #[test]
+    fn trimmed() {
+        // Long stem overlaps a small repeat in its inner segment
+        let ir = repeats(vec![
+            vec![(0..10, 50..60), (20..25, 35..40)],
+            vec![(22..24, 30..32)],
+        ]);
+        let scores = vec![15, 6];
+        assert_eq!(run(&ir, &scores).1, 15);
+
+        let solve = |scores: &[i32], trimming| {
+            let options = Options::new().with_trimming(trimming);
+            let (_, repeats, score) = run_with(&ir, scores, &options).unwrap().dissolve();
+            (repeats, score)
+        };
+        let (result, score) = solve(&scores, Trimming::Segments);
+        assert_eq!(score, 16);
+        assert_eq!(result, vec![
+            (0, repeats(vec![vec![(0..10, 50..60)]]).remove(0)),
+            (1, ir[1].clone()),
+        ]);
+
+        let (result, score) = solve(&scores, Trimming::Basepairs);
+        assert_eq!(score, 18);
+        assert_eq!(result, vec![
+            (0, repeats(vec![vec![(0..10, 50..60), (20..22, 38..40)]]).remove(0)),
+            (1, ir[1].clone()),
+        ]);
+
+        // Untouched repeats are reported as is
+        let (result, score) = solve(&[15, 0], Trimming::Basepairs);
+        assert_eq!((result, score), (vec![(0, ir[0].clone())], 15));
+    }
+
+    #[test]
+    fn trimmed_coherent() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 31..32), (24..25, 29..30)],
+            vec![(20..22, 30..32), (24..26, 27..29)],
+            vec![(1..8, 40..47)],
+            vec![(5..11, 33..39)],
+        ]);
+        let scores = vec![3, 1, 2, 1, 4, 6, 5, 7];
+        let (_, optimum) = run(&ir, &scores);
+
+        for trimming in [Trimming::Segments, Trimming::Basepairs] {
+            let options = Options::new().with_trimming(trimming);
+            let (_, result, score) = run_with(&ir, &scores, &options).unwrap().dissolve();
+            assert!(score >= optimum);
+
+            for ((_, a), (_, b)) in result.iter().tuple_combinations() {
+                assert!(conflict::conflict(a, b).is_none(), "{a:?} vs {b:?}");
+            }
+            for (rnaid, piece) in &result {
+                let source = ir[*rnaid].basepairs().collect_vec();
+                assert!(piece.basepairs().all(|x| source.contains(&x)));
+            }
+        }
+    }
+
+    #[test]
+    fn weighted() {
+        let ir = repeats(vec![
+            vec![(0..10, 50..60), (20..25, 35..40)],
+            vec![(22..24, 30..32)],
+        ]);
+        let mut track = vec![1; 60];
+        for x in [22, 23, 30, 31] {
+            track[x] = 5;
+        }
+        let weights = Weights::new(track, 0, -3);
+        assert_eq!(weights.scores(&ir), vec![35, 17]);
+        let options = Options::new().with_weights(weights);
+        let (selected, _, score) = run_with(&ir, &[0, 0], &options).unwrap().dissolve();
+        assert_eq!((selected, score), (vec![0], 35));
+
+        // Shared positions are scored consistently, i.e. the inner segment loses its best part
+        let options = options.with_trimming(Trimming::Basepairs);
+        let (_, result, score) = run_with(&ir, &[0, 0], &options).unwrap().dissolve();
+        assert_eq!(score, 39);
+        assert_eq!(result, vec![
+            (0, repeats(vec![vec![(0..10, 50..60), (20..22, 38..40)]]).remove(0)),
+            (1, ir[1].clone()),
+        ]);
+
+        // Positions outside of the track have zero weight
+        assert_eq!(Weights::new(vec![1; 10], 20, 0).score(&ir[1]), 2);
+        assert_eq!(Weights::new(vec![1; 10], 20, 0).score(&ir[0]), 5);
+    }
+
+    #[test]
+    fn tolerant() {
+        // Stems overlap by 2 nucleotides
+        let ir = repeats(vec![
+            vec![(0..10, 50..60)],
+            vec![(8..15, 30..37)],
+            vec![(38..40, 45..47)],
+        ]);
+        let scores = vec![5, 3, 1];
+        assert_eq!(run(&ir, &scores), (vec![0, 2], 6));
+
+        let solve = |ir: &[inv::Repeat<isize>], scores: &[i32], tolerance| {
+            let options = Options::new().with_tolerance(tolerance);
+            let (_, repeats, score) = run_with(ir, scores, &options).unwrap().dissolve();
+            (repeats, score)
+        };
+        let untouched = vec![(0, ir[0].clone()), (2, ir[2].clone())];
+        assert_eq!(solve(&ir, &scores, 0), (untouched.clone(), 6));
+
+        // The overlap exceeds the tolerance
+        assert_eq!(solve(&ir, &scores, 1), (untouched, 6));
+
+        // Lower-scoring stem is trimmed just enough to resolve the overlap
+        let expected = vec![
+            (0, ir[0].clone()), (1, repeats(vec![vec![(10..15, 30..35)]]).remove(0)), (2, ir[2].clone()),
+        ];
+        assert_eq!(solve(&ir, &scores, 2), (expected.clone(), 9));
+        assert_eq!(solve(&ir, &scores, 3), (expected, 9));
+
+        // Higher-scoring stem keeps its nucleotides
+        let (result, score) = solve(&ir, &[3, 5, 1], 2);
+        assert_eq!(score, 9);
+        assert_eq!(result[0], (0, repeats(vec![vec![(0..8, 52..60)]]).remove(0)));
+        assert_eq!(result[1], (1, ir[1].clone()));
+
+        // The tolerance applies to each pair of overlapping repeats
+        let ir = repeats(vec![
+            vec![(5..11, 50..56)],
+            vec![(10..20, 40..50)],
+            vec![(19..22, 25..28)],
+        ]);
+        let expected = vec![
+            (0, ir[0].clone()), (1, repeats(vec![vec![(11..19, 41..49)]]).remove(0)), (2, ir[2].clone()),
+        ];
+        assert_eq!(solve(&ir, &[3, 2, 3], 1), (expected, 8));
+
+        assert_eq!(solve(&ir[..0], &scores[..0], 2), (vec![], 0));
+    }
+
+    #[test]
+    fn pseudoknots() {
+        let ir = repeats(vec![
+            vec![(0..5, 20..25)],
+            vec![(10..15, 30..35)],
+            // Hairpin inside the pseudoknot loop
+            vec![(16..17, 18..19)],
+            // Crosses the pseudoknot
+            vec![(26..27, 40..41)],
+        ]);
+        let scores = vec![5, 5, 1, 2];
+
+        let (mut result, score) = run(&ir, &scores);
+        result.sort();
+        assert_eq!((result, score), (vec![0, 2, 3], 8));
+
+        let (mut result, crossing, score) = run_pseudoknots(&ir, &scores, 2);
+        result.sort();
+        assert_eq!((result, crossing, score), (vec![0, 1, 2], vec![(0, 1)], 9));
+
+        // Penalty is too high
+        let (mut result, crossing, score) = run_pseudoknots(&ir, &scores, 4);
+        result.sort();
+        assert_eq!((result, crossing, score), (vec![0, 2, 3], vec![], 8));
+
+        assert_eq!(run_pseudoknots(&ir[..0], &scores[..0], 2), (vec![], vec![], 0));
+    }
+
+    #[test]
+    fn deeply_nested() {
+        // Each repeat is nested in the inner gap of the previous one
+        let depth = 2000;
+        let ir = (0..depth)
+            .map(|x| inv::Repeat::new(vec![inv::Segment::new(x..x + 1, 2 * depth - x - 1..2 * depth - x)]))
+            .collect_vec();
+        let scores = (0..depth).map(|x| if x % 2 == 0 { 1 } else { 2 }).collect_vec();
+
+        let (solution, score) = run(&ir, &scores);
+        assert_eq!(solution.len(), depth as usize);
+        assert_eq!(score, 3 * depth / 2);
+    }
+
+    #[test]
+    fn components() {
+        let mut ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 31..32), (24..25, 29..30)],
+            vec![(20..22, 30..32), (24..26, 27..29)],
+            vec![(40..45, 50..55)],
+            // Negative repeat doesn't merge clusters
+            vec![(0..1, 60..61)],
+        ]);
+        let mut scores = vec![3, 1, 2, 1, 4, 6, 2, -5];
+        // Shifted copies with different scores
+        for shift in [100, 200, 300] {
+            for x in 0..8 {
+                let mut repeat = ir[x].clone();
+                repeat.shift(&shift);
+                ir.push(repeat);
+                scores.push(scores[x] * (shift as Score / 100) % 7 - 1);
+            }
+        }
+
+        let clusters = components::clusters(&ir, &scores);
+        assert_eq!(clusters[..2], [vec![0, 1, 2, 3, 4, 5], vec![6]]);
+        assert_eq!(clusters.len(), 8);
+
+        let (mut expected, escore) = dynprog::DynProgSolution::new().solve(&ir, &scores);
+        expected.sort();
+        assert_eq!(run(&ir, &scores), (expected, escore));
+
+        #[cfg(feature = "parallel")]
+        assert_eq!(run_parallel(&ir, &scores), run(&ir, &scores));
+    }
+
+    #[test]
+    fn strategies() {
+        let mut ir = repeats(vec![
+            // Greedy takes the long stem instead of two short ones
+            vec![(0..10, 40..50)],
+            vec![(0..4, 10..14)],
+            vec![(40..44, 60..64)],
+            vec![(20..22, 25..27)],
+            vec![(21..23, 30..32)],
+            vec![(70..72, 80..82), (74..75, 77..78)],
+            vec![(75..76, 90..91)],
+        ]);
+        let mut scores = vec![3, 2, 2, 1, 1, 2, 1];
+
+        let solve = |ir: &[inv::Repeat<isize>], scores: &[Score], strategy| {
+            let options = Options::new().with_strategy(strategy);
+            let (selected, _, score) = run_with(ir, scores, &options).unwrap().dissolve();
+            (selected, score)
+        };
+        assert_eq!(solve(&ir, &scores, Strategy::Exact), run(&ir, &scores));
+        assert_eq!(solve(&ir, &scores, Strategy::Greedy), (vec![0, 3, 5], 6));
+        assert_eq!(run(&ir, &scores), (vec![1, 2, 3, 5], 7));
+
+        // Windows cover all repeats => the exact solution
+        let windowed = |size, overlap| Strategy::Windowed { size, overlap };
+        assert_eq!(solve(&ir, &scores, windowed(100, 10)), run(&ir, &scores));
+        // Long repeats are skipped
+        assert_eq!(solve(&ir, &scores, windowed(20, 5)), (vec![1, 3, 5], 5));
+
+        // Approximations are always coherent and never beat the optimum
+        for shift in [100, 200] {
+            for x in 0..7 {
+                let mut repeat = ir[x].clone();
+                repeat.shift(&shift);
+                ir.push(repeat);
+                scores.push(scores[x] * shift as Score / 100 % 5);
+            }
+        }
+        let (_, optimum) = run(&ir, &scores);
+        for strategy in [Strategy::Greedy, windowed(30, 10), windowed(60, 0), windowed(55, 54)] {
+            let (solution, score) = solve(&ir, &scores, strategy);
+            assert!(score <= optimum);
+            assert_eq!(solution.iter().map(|x| scores[*x]).sum::<Score>(), score);
+            for (a, b) in solution.iter().tuple_combinations() {
+                assert!(conflict::conflict(&ir[*a], &ir[*b]).is_none(), "{strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn tie_breaking() {
+        let ir = repeats(vec![
+            // A single long stem vs two short ones
+            vec![(0..10, 40..50)],
+            vec![(0..4, 10..14)],
+            vec![(40..44, 60..64)],
+            // Equivalent alternatives
+            vec![(100..105, 110..115)],
+            vec![(101..106, 112..117)],
+            vec![(120..122, 125..127)],
+            vec![(121..123, 126..128)],
+            vec![(118..119, 130..131)],
+        ]);
+        let scores = vec![2, 1, 1, 1, 1, 1, 1, 1];
+
+        let solve = |ir: &[inv::Repeat<isize>], scores: &[i32], policy| {
+            let (selected, _, score) = run_with(ir, scores, &Options::new().with_ties(policy)).unwrap().dissolve();
+            (selected, score)
+        };
+        let (fewest, score) = solve(&ir, &scores, TieBreaking::Fewest);
+        assert_eq!((fewest.clone(), score), (vec![0, 3, 5, 7], 5));
+        let (most, score) = solve(&ir, &scores, TieBreaking::Most);
+        assert_eq!((most.clone(), score), (vec![1, 2, 3, 5, 7], 5));
+
+        // Same repeats are selected for any permutation of the input
+        let mut permutation = (0..ir.len()).collect_vec();
+        for step in 0..20 {
+            permutation.rotate_left(step % 3 + 1);
+            permutation.swap(step % ir.len(), (step * 5 + 3) % ir.len());
+
+            let pir = permutation.iter().map(|x| ir[*x].clone()).collect_vec();
+            let pscores = permutation.iter().map(|x| scores[*x]).collect_vec();
+            for (policy, expected) in [(TieBreaking::Fewest, &fewest), (TieBreaking::Most, &most)] {
+                let (solution, score) = solve(&pir, &pscores, policy);
+                assert_eq!(score, 5);
+                assert_eq!(solution.iter().map(|x| permutation[*x]).sorted().collect_vec(), *expected);
+            }
+        }
+        assert_eq!(solve(&ir[..0], &scores[..0], TieBreaking::Most), (vec![], 0));
+    }
+
+    #[test]
+    fn grouped() {
+        // Same coordinates on different contigs
+        let ir = repeats(vec![
+            vec![(0..5, 20..25)],
+            vec![(10..15, 30..35)],
+            vec![(0..5, 20..25)],
+            vec![(10..15, 30..35)],
+            vec![(40..45, 50..55)],
+        ]);
+        let scores = vec![3, 2, 1, 4, 5];
+        let groups = vec!["chr2", "chr2", "chr1", "chr1", "chr2"];
+
+        let expected = vec![("chr2", vec![0, 4], 8), ("chr1", vec![3], 4)];
+        assert_eq!(run_grouped(&ir, &scores, &groups), expected);
+
+        #[cfg(feature = "parallel")]
+        assert_eq!(run_grouped_parallel(&ir, &scores, &groups), expected);
+
+        assert_eq!(run_grouped(&ir[..0], &scores[..0], &groups[..0]), vec![]);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 31..32), (24..25, 29..30)],
+            vec![(20..22, 30..32), (24..26, 27..29)],
+            vec![(40..45, 50..55)],
+            vec![(1..8, 40..47)],
+        ]);
+        let mut scores = vec![3, 1, 2, 1, 4, 6, 2, -5];
+        for shift in [100, 200] {
+            for x in 0..8 {
+                let mut repeat = ir[x].clone();
+                repeat.shift(&shift);
+                ir.push(repeat);
+                scores.push(scores[x] * shift as Score / 100 % 7 - 1);
+            }
+        }
+
+        let mut optimizer = Optimizer::new();
+        let mut removed = vec![false; ir.len()];
+        let check = |optimizer: &mut Optimizer<isize, Score>, scores: &[Score], removed: &[bool]| {
+            let active = (0..ir.len()).filter(|x| !removed[*x] && *x < scores.len()).collect_vec();
+            let subset = active.iter().map(|x| &ir[*x]).collect_vec();
+            let subscores = active.iter().map(|x| scores[*x]).collect_vec();
+            let (_, expected) = run(&subset, &subscores);
+
+            let (solution, score) = optimizer.solve();
+            assert_eq!(score, expected);
+            assert_eq!(solution.iter().map(|x| scores[*x]).sum::<Score>(), score);
+            assert!(solution.iter().all(|x| active.contains(x)));
+            for (a, b) in solution.iter().tuple_combinations() {
+                assert!(conflict::conflict(&ir[*a], &ir[*b]).is_none());
+            }
+        };
+
+        // Repeats are added in batches, merging previously independent clusters
+        for (rnaid, (repeat, score)) in ir.iter().zip(&scores).enumerate() {
+            assert_eq!(optimizer.add(repeat.clone(), *score), rnaid);
+            if rnaid % 5 == 4 {
+                check(&mut optimizer, &scores[..=rnaid], &removed);
+            }
+        }
+        check(&mut optimizer, &scores, &removed);
+
+        for step in 0..30 {
+            let rnaid = (step * 7) % ir.len();
+            if step % 4 == 3 && !removed[rnaid] {
+                optimizer.remove(rnaid);
+                removed[rnaid] = true;
+            } else if !removed[rnaid] {
+                scores[rnaid] = (step as Score * 3) % 11 - 3;
+                optimizer.update(rnaid, scores[rnaid]);
+            }
+            check(&mut optimizer, &scores, &removed);
+        }
+    }
+
+    #[test]
+    fn options() {
+        let ir = repeats(vec![
+            vec![(0..2, 30..32), (7..9, 25..27)],
+            vec![(3..4, 5..6)],
+            vec![(10..12, 19..21), (15..16, 18..19)],
+            vec![(12..13, 14..15)],
+            vec![(22..23, 23..24)],
+            vec![(40..45, 50..55)],
+            vec![(9..10, 21..22)],
+        ]);
+        let scores = vec![3, 1, 2, 1, 1, 2, -1];
+
+        // Nesting chain 0 > 6 > 2 > 3, repeat 4 has no loop
+        let limits = Limits::new(None, Some(2), Some(1));
+        let solve = |include, exclude| {
+            let options = Options::new()
+                .with_constraints(Constraints::new(include, exclude, vec![]))
+                .with_limits(limits.clone());
+            run_with(&ir, &scores, &options).map(|x| (x.selected().clone(), *x.score()))
+        };
+
+        assert_eq!(solve(vec![], vec![]), Ok((vec![0, 1, 2, 5], 8)));
+        assert_eq!(solve(vec![6], vec![]), Ok((vec![0, 1, 5, 6], 5)));
+        assert_eq!(solve(vec![3], vec![0]), Ok((vec![1, 2, 3, 5], 6)));
+        assert_eq!(solve(vec![0, 3], vec![]), Ok((vec![0, 1, 3, 5], 7)));
+
+        assert_eq!(solve(vec![4], vec![]), Err(ConstraintError::Limited(4)));
+        assert_eq!(solve(vec![0, 2, 3], vec![]), Err(ConstraintError::Depth));
+        assert_eq!(solve(vec![0, 1], vec![1]), Err(ConstraintError::Contradiction(1)));
+
+        let (selected, _, score) = run_with(&ir, &scores, &Options::new()).unwrap().dissolve();
+        assert_eq!((selected, score), run(&ir, &scores));
+    }
+
+    #[test]
+    fn combined() {
+        let ir = repeats(vec![
+            vec![(0..10, 40..50)],
+            vec![(0..4, 10..14)],
+            vec![(40..44, 60..64)],
+            vec![(100..105, 110..115)],
+            vec![(101..106, 112..117)],
+        ]);
+        let scores = vec![2, 1, 1, 1, 1];
+        let solve = |options: Options<isize, i32>| {
+            let (selected, _, score) = run_with(&ir, &scores, &options).unwrap().dissolve();
+            (selected, score)
+        };
+
+        let exclude = Constraints::new(vec![], vec![3], vec![]);
+        assert_eq!(solve(Options::new().with_ties(TieBreaking::Most)), (vec![1, 2, 3], 3));
+        assert_eq!(
+            solve(Options::new().with_ties(TieBreaking::Most).with_constraints(exclude.clone())), (vec![1, 2, 4], 3)
+        );
+        assert_eq!(
+            solve(Options::new().with_ties(TieBreaking::Fewest).with_constraints(exclude)), (vec![0, 4], 3)
+        );
+
+        // Included repeats are never trimmed
+        let ir = repeats(vec![
+            vec![(0..10, 50..60), (20..25, 35..40)],
+            vec![(22..24, 30..32)],
+        ]);
+        let options = Options::new()
+            .with_trimming(Trimming::Segments)
+            .with_constraints(Constraints::new(vec![0], vec![], vec![]));
+        let (selected, result, score) = run_with(&ir, &[15, 6], &options).unwrap().dissolve();
+        assert_eq!((selected, result, score), (vec![0], vec![(0, ir[0].clone())], 15));
+
+        // Limits are applied before resolving overlaps
+        let ir = repeats(vec![
+            vec![(0..10, 50..60)],
+            vec![(8..15, 30..37)],
+            vec![(38..40, 45..47)],
+        ]);
+        let options = Options::new().with_tolerance(2).with_limits(Limits::new(Some(30), None, None));
+        let (selected, result, score) = run_with(&ir, &[5, 3, 1], &options).unwrap().dissolve();
+        assert_eq!(selected, vec![1, 2]);
+        assert_eq!((result, score), (vec![(1, ir[1].clone()), (2, ir[2].clone())], 4));
+    }
 }
@@ -0,0 +1,219 @@
+use std::borrow::Borrow;
+use std::fmt::Debug;
+
+use derive_getters::{Dissolve, Getters};
+use itertools::Itertools;
+
+use super::{approx, components, inv, score, ties, tolerance, trim};
+use super::approx::Strategy;
+use super::constraints::{ConstraintError, Constraints};
+use super::limits::Limits;
+use super::ties::TieBreaking;
+use super::trim::Trimming;
+use super::weights::Weights;
+
+/// Settings of the optimization, see `run_with`.
+#[derive(Clone, Debug, PartialEq, Getters, Dissolve)]
+pub struct Options<Idx: inv::Coordinate, Score: score::Score> {
+    constraints: Constraints<Idx>,
+    limits: Limits<Idx>,
+    strategy: Strategy<Idx>,
+    // Ties are resolved by the input order if not set
+    ties: Option<TieBreaking>,
+    // Repeats are selected as a whole if not set
+    trimming: Option<Trimming>,
+    // Maximum number of nucleotides shared by two selected repeats
+    tolerance: Idx,
+    // Per-nucleotide model added to the scores of repeats
+    weights: Option<Weights<Idx, Score>>,
+}
+
+impl<Idx: inv::Coordinate, Score: score::Score> Options<Idx, Score> {
+    /// Exact optimization without any constraints, i.e. the same as `run`.
+    pub fn new() -> Self {
+        Self {
+            constraints: Constraints::new(vec![], vec![], vec![]),
+            limits: Limits::new(None, None, None),
+            strategy: Strategy::Exact,
+            ties: None,
+            trimming: None,
+            tolerance: Idx::zero(),
+            weights: None,
+        }
+    }
+
+    pub fn with_constraints(mut self, constraints: Constraints<Idx>) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits<Idx>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy<Idx>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_ties(mut self, policy: TieBreaking) -> Self {
+        self.ties = Some(policy);
+        self
+    }
+
+    pub fn with_trimming(mut self, trimming: Trimming) -> Self {
+        self.trimming = Some(trimming);
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Idx) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_weights(mut self, weights: Weights<Idx, Score>) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+}
+
+impl<Idx: inv::Coordinate, Score: score::Score> Default for Options<Idx, Score> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Coherent set of repeats found by `run_with`.
+#[derive(Clone, Debug, PartialEq, Getters, Dissolve)]
+pub struct Solution<Idx: inv::Coordinate, Score: score::Score> {
+    // Indices of the selected repeats in the ascending order
+    selected: Vec<usize>,
+    // Selected (possibly trimmed) repeats with their indices, a trimmed repeat might be reported as several pieces
+    repeats: Vec<(usize, inv::Repeat<Idx>)>,
+    score: Score,
+}
+
+pub fn run<Idx, IR, Score>(
+    ir: &[IR], scores: &[Score], options: &Options<Idx, Score>,
+) -> Result<Solution<Idx, Score>, ConstraintError>
+    where
+        Idx: inv::Coordinate + Debug,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let (constraints, limits) = (&options.constraints, &options.limits);
+    assert!(options.tolerance >= Idx::zero(), "Overlap tolerance must not be negative");
+    assert!(
+        options.trimming.is_none() || options.tolerance == Idx::zero(),
+        "Trimming can't be combined with the overlap tolerance"
+    );
+    assert!(
+        options.trimming.is_none() || limits.max_depth().is_none(),
+        "Nesting depth of trimmed repeats can't be limited"
+    );
+    match options.strategy {
+        Strategy::Exact => {}
+        Strategy::Greedy | Strategy::Windowed { .. } => assert!(
+            constraints.include().is_empty() && limits.max_depth().is_none(),
+            "Included repeats and the nesting depth are supported only by the exact strategy"
+        ),
+    }
+    if let Strategy::Windowed { size, overlap } = options.strategy {
+        assert!(Idx::zero() <= overlap && overlap < size, "Window overlap must be in [0, size)");
+    }
+
+    let include = constraints.included(ir)?;
+    if let Some(&x) = include.iter().find(|x| !limits.allows(ir[**x].borrow())) {
+        return Err(ConstraintError::Limited(x));
+    }
+
+    let mut included = vec![false; ir.len()];
+    for &x in &include {
+        included[x] = true;
+    }
+    let mut excluded = vec![false; ir.len()];
+    for &x in constraints.exclude() {
+        excluded[x] = true;
+    }
+
+    let allowed = (0..ir.len())
+        .filter(|x| {
+            included[*x] || (!excluded[*x] && constraints.allows(ir[*x].borrow()) && limits.allows(ir[*x].borrow()))
+        })
+        .collect_vec();
+    let subset = allowed.iter().map(|x| ir[*x].borrow()).collect_vec();
+    let subscores = allowed.iter().map(|x| scores[*x]).collect_vec();
+
+    // Candidates are either repeats themselves or their trimmed versions, each one is derived from a single repeat
+    let mut complete = true;
+    let mut solve = |candidates: &[&inv::Repeat<Idx>], sources: &[usize], scores: &[Score]| {
+        let included = sources.iter().map(|x| included[allowed[*x]]).collect_vec();
+        let (selected, (count, score)) = self::solve(candidates, &included, scores, options);
+        complete = count == included.iter().filter(|x| **x).count();
+        (selected, score)
+    };
+    let (repeats, score) = match (options.trimming, &options.weights) {
+        (Some(trimming), weights) => trim::run(&subset, &subscores, weights.as_ref(), trimming, solve),
+        (None, weights) => {
+            let subscores = match weights {
+                Some(w) => subset.iter().zip(subscores).map(|(r, s)| s.add(w.score(r))).collect(),
+                None => subscores,
+            };
+            if options.tolerance > Idx::zero() {
+                tolerance::run(&subset, &subscores, options.tolerance, solve)
+            } else {
+                let (selected, score) = solve(&subset, &(0..subset.len()).collect_vec(), &subscores);
+                (selected.into_iter().map(|x| (x, subset[x].clone())).collect(), score)
+            }
+        }
+    };
+
+    // Included candidates are the primary objective => the DP selects all of them whenever they fit into the nesting
+    // depth, and other candidates must be coherent with them
+    if !complete {
+        return Err(ConstraintError::Depth);
+    }
+    let repeats = repeats.into_iter().map(|(x, repeat)| (allowed[x], repeat)).collect_vec();
+    let selected = repeats.iter().map(|x| x.0).dedup().collect();
+    Ok(Solution { selected, repeats, score })
+}
+
+// Select a coherent set of candidates with the given strategy. Tie-breakers are the last objectives, i.e. they never
+// outweigh the score itself. Returns the selected candidates in the ascending order, the number of included ones among
+// them and the total score.
+fn solve<Idx, Score>(
+    candidates: &[&inv::Repeat<Idx>], included: &[bool], scores: &[Score], options: &Options<Idx, Score>,
+) -> (Vec<usize>, (usize, Score))
+    where
+        Idx: inv::Coordinate,
+        Score: score::Score
+{
+    let mut order = (0..candidates.len()).filter(|x| included[*x] || scores[*x].exceeds(Score::zero())).collect_vec();
+    let ties = match options.ties {
+        Some(policy) => {
+            let canonical = ties::canonical(&order.iter().map(|x| candidates[*x]).collect_vec());
+            order = canonical.into_iter().map(|x| order[x]).collect();
+            ties::objectives(&order.iter().map(|x| candidates[*x]).collect_vec(), policy)
+        }
+        None => vec![(0, 0); order.len()],
+    };
+
+    let subset = order.iter().map(|x| candidates[*x]).collect_vec();
+    let objectives = order.iter()
+        .zip(ties)
+        .map(|(x, (count, offset))| (included[*x] as usize, scores[*x], count, offset))
+        .collect_vec();
+    let (solution, (count, score, _, _)) = match options.strategy {
+        Strategy::Exact => {
+            let results = components::clusters(&subset, &objectives)
+                .into_iter()
+                .map(|x| components::solve(&subset, &objectives, &x, *options.limits.max_depth()))
+                .collect();
+            components::merge(results)
+        }
+        Strategy::Greedy => approx::greedy(&subset, &objectives),
+        Strategy::Windowed { size, overlap } => approx::windowed(&subset, &objectives, size, overlap),
+    };
+    (solution.into_iter().map(|x| order[x]).sorted().collect(), (count, score))
+}
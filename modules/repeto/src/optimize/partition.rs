@@ -0,0 +1,160 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+
+use derive_getters::{Dissolve, Getters};
+
+use super::{index, inv, score};
+use super::dynprog::Workload;
+use super::table::Table;
+
+/// Boltzmann ensemble of coherent repeat sets.
+#[derive(Clone, Debug, PartialEq, Getters, Dissolve)]
+pub struct Ensemble<Idx: inv::Coordinate> {
+    // Natural logarithm of the partition function
+    logz: f64,
+    // Marginal inclusion probability of each repeat
+    probabilities: Vec<f64>,
+    // Sequence range covered by all repeats
+    range: Range<Idx>,
+    // Pairing probability of each nucleotide in the range
+    pairing: Vec<f64>,
+}
+
+pub fn logaddexp(a: f64, b: f64) -> f64 {
+    if a == f64::NEG_INFINITY {
+        return b;
+    } else if b == f64::NEG_INFINITY {
+        return a;
+    }
+    a.max(b) + (-(a - b).abs()).exp().ln_1p()
+}
+
+// Log partition functions of all required cells, i.e. log sum of weights of all coherent sets inside each cell.
+// Unlike the optimization, each set must be counted exactly once. It's guaranteed by the rightmost repeat in the set:
+// it's unique (repeats with the same end always overlap), and all other repeats are either nested in its gaps or
+// located before it.
+pub struct Inside<Idx: inv::Coordinate> {
+    pub w: Workload<Idx, f64>,
+    pub extents: Vec<Option<(usize, usize)>>,
+    // Cells outside of the stored bands contain only the empty set => log(1) = 0
+    pub table: Table<f64>,
+}
+
+impl<Idx: inv::Coordinate> Inside<Idx> {
+    pub fn new(index: index::Index<Idx>, weights: &[f64]) -> Self {
+        let w = Workload::new(index, weights);
+        let extents = w.extents();
+
+        let mut inside = Self { w, extents, table: Table::new(0.0) };
+        inside.table.reset(inside.extents.iter().copied());
+        for sind in (0..inside.extents.len()).rev() {
+            if let Some((first, last)) = inside.extents[sind] {
+                for eind in first..=last {
+                    let mut logz = if eind > 0 { inside.table.get(sind, eind - 1) } else { 0.0 };
+                    for &rnaid in &inside.w.index.ends()[eind].repeats {
+                        if inside.w.index.revmap(rnaid).0 >= sind {
+                            logz = logaddexp(logz, inside.term(sind, rnaid, |_| {}));
+                        }
+                    }
+                    inside.table.set(sind, eind, logz);
+                }
+            }
+        }
+        inside
+    }
+
+    pub fn logz(&self) -> f64 {
+        self.table.get(0, self.w.index.ends().len() - 1)
+    }
+
+    // Log weight of all sets in the row where the RNA is the rightmost one.
+    // Cells that contribute to the weight are reported to the callback.
+    pub fn term(&self, sind: usize, rnaid: usize, mut included: impl FnMut((usize, usize))) -> f64 {
+        let mut logw = self.w.scores[rnaid];
+        for &cell in &self.w.gaps[rnaid] {
+            logw += self.table.get(cell.0, cell.1);
+            included(cell);
+        }
+        if let Some(preeind) = self.w.pre[rnaid] {
+            logw += self.table.get(sind, preeind);
+            included((sind, preeind));
+        }
+        logw
+    }
+
+    // Log of marginal inclusion probabilities, computed by propagating the outside weights in the reverse order
+    pub fn outside(&self) -> Vec<f64> {
+        let logz = self.logz();
+        let mut marginals = vec![f64::NEG_INFINITY; self.w.scores.len()];
+
+        let mut outside = Table::new(f64::NEG_INFINITY);
+        outside.reset(self.extents.iter().copied());
+        outside.set(0, self.w.index.ends().len() - 1, 0.0);
+
+        for (sind, extent) in self.extents.iter().enumerate() {
+            let (first, last) = match extent {
+                Some(x) => *x,
+                None => continue,
+            };
+            for eind in (first..=last).rev() {
+                let logo = outside.get(sind, eind);
+                if logo == f64::NEG_INFINITY {
+                    continue;
+                }
+                if eind > first {
+                    outside.set(sind, eind - 1, logaddexp(outside.get(sind, eind - 1), logo));
+                }
+
+                for &rnaid in &self.w.index.ends()[eind].repeats {
+                    if self.w.index.revmap(rnaid).0 < sind {
+                        continue;
+                    }
+                    let logt = logo + self.term(sind, rnaid, |_| {});
+                    marginals[rnaid] = logaddexp(marginals[rnaid], logt - logz);
+
+                    // Cells before the row start are constant and don't need outside weights
+                    self.term(sind, rnaid, |(s, e)| {
+                        if matches!(self.extents[s], Some((first, _)) if first <= e) {
+                            let value = logaddexp(outside.get(s, e), logt - self.table.get(s, e));
+                            outside.set(s, e, value);
+                        }
+                    });
+                }
+            }
+        }
+        marginals
+    }
+}
+
+pub fn partition<Idx, IR, Score>(ir: &[IR], scores: &[Score], temperature: f64) -> Ensemble<Idx>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    if ir.is_empty() {
+        return Ensemble { logz: 0.0, probabilities: vec![], range: Idx::zero()..Idx::zero(), pairing: vec![] };
+    }
+
+    let weights: Vec<_> = scores.iter().map(|x| x.to_f64() / temperature).collect();
+    let inside = Inside::new(index::Index::new(ir), &weights);
+    let probabilities: Vec<_> = inside.outside().into_iter().map(|x| x.exp().min(1.0)).collect();
+
+    let range = inside.w.index.starts()[0].pos..inside.w.index.ends().last().unwrap().pos;
+    let length = (range.end - range.start).to_usize().unwrap();
+    let mut pairing = vec![0.0; length + 1];
+    for (repeat, probability) in ir.iter().zip(&probabilities) {
+        for block in repeat.borrow().seqranges() {
+            pairing[(block.start - range.start).to_usize().unwrap()] += probability;
+            pairing[(block.end - range.start).to_usize().unwrap()] -= probability;
+        }
+    }
+    let mut cumsum = 0.0;
+    for x in &mut pairing {
+        cumsum += *x;
+        *x = cumsum.clamp(0.0, 1.0);
+    }
+    pairing.pop();
+
+    Ensemble { logz: inside.logz(), probabilities, range, pairing }
+}
@@ -0,0 +1,83 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use super::{dynprog, index, score};
+use super::inv;
+
+// Repeats form two non-nested arc groups: left(a) < left(b) < right(a) < right(b)
+fn htype<Idx: inv::Coordinate>(a: &inv::Repeat<Idx>, b: &inv::Repeat<Idx>) -> bool {
+    a.left_brange().end <= b.left_brange().start &&
+        b.left_brange().end <= a.right_brange().start &&
+        a.right_brange().end <= b.right_brange().start
+}
+
+// Pairs (a, b) of repeats with overlapping bounding ranges and start(a) <= start(b), ordered by their ids. Repeats are
+// swept by their start, i.e. each repeat is compared only with the following ones that start inside its bounding range.
+fn overlapping<Idx, IR>(ir: &[IR]) -> Vec<(usize, usize)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>
+{
+    let order = (0..ir.len()).sorted_by_key(|x| ir[*x].borrow().brange().start).collect_vec();
+
+    let mut pairs = Vec::new();
+    for (i, &a) in order.iter().enumerate() {
+        let end = ir[a].borrow().brange().end;
+        pairs.extend(
+            order[i + 1..].iter()
+                .take_while(|b| ir[**b].borrow().brange().start < end)
+                .map(|&b| (a, b))
+        );
+    }
+    pairs.sort_by_key(|(a, b)| (*a.min(b), *a.max(b)));
+    pairs
+}
+
+pub fn run<Idx, IR, Score>(ir: &[IR], scores: &[Score], penalty: Score) -> (Vec<usize>, Vec<(usize, usize)>, Score)
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    if ir.is_empty() {
+        return (vec![], vec![], Score::zero());
+    }
+
+    let mut blocks: Vec<Vec<Range<Idx>>> = ir.iter()
+        .map(|x| x.borrow().seqranges().cloned().collect())
+        .collect();
+    let mut units = scores.to_vec();
+
+    // Each H-type pseudoknot is a single unit with blocks of both repeats. Nested-only repeats can be placed in
+    // any gap between them, e.g. inside pseudoknot loops.
+    let mut pseudoknots = Vec::new();
+    for (a, b) in overlapping(ir) {
+        let (ra, rb) = (ir[a].borrow(), ir[b].borrow());
+        if !htype(ra, rb) {
+            continue;
+        }
+
+        let score = scores[a].add(scores[b]).sub(penalty);
+        if score.exceeds(Score::zero()) {
+            blocks.push(ra.seqranges().merge_by(rb.seqranges(), |x, y| x.start <= y.start).cloned().collect());
+            units.push(score);
+            pseudoknots.push((a, b));
+        }
+    }
+
+    let (solution, score) = dynprog::DynProgSolution::new().solve_index(index::Index::from_blocks(blocks), &units);
+
+    let (mut selected, mut crossing) = (Vec::new(), Vec::new());
+    for unit in solution {
+        if unit < ir.len() {
+            selected.push(unit);
+        } else {
+            let (a, b) = pseudoknots[unit - ir.len()];
+            selected.extend([a, b]);
+            crossing.push((a, b));
+        }
+    }
+    (selected, crossing, score)
+}
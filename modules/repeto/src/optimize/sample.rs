@@ -0,0 +1,80 @@
+use std::borrow::Borrow;
+
+use super::{index, inv, score};
+use super::partition::Inside;
+
+// SplitMix64 generator: tiny, fast and reproducible across platforms and releases
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self { Self { state: seed } }
+
+    // Uniform sample from [0, 1)
+    fn uniform(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Stochastic traceback: each cell is either copied from the previous end or split by the rightmost repeat,
+// with probabilities proportional to the weights of the corresponding sets.
+fn traceback<Idx: inv::Coordinate>(inside: &Inside<Idx>, rng: &mut Rng) -> Vec<usize> {
+    let mut selected = Vec::new();
+    let mut queue = vec![(0, inside.w.index.ends().len() - 1)];
+    while let Some((sind, eind)) = queue.pop() {
+        // Only the empty set is located before the row start
+        if !matches!(inside.extents[sind], Some((first, _)) if first <= eind) {
+            continue;
+        }
+
+        let logz = inside.table.get(sind, eind);
+        let mut threshold = rng.uniform();
+
+        let mut chosen = None;
+        for &rnaid in &inside.w.index.ends()[eind].repeats {
+            if inside.w.index.revmap(rnaid).0 < sind {
+                continue;
+            }
+            threshold -= (inside.term(sind, rnaid, |_| {}) - logz).exp();
+            if threshold < 0.0 {
+                chosen = Some(rnaid);
+                break;
+            }
+        }
+
+        match chosen {
+            // Copy of the previous end, it also absorbs rounding errors
+            None if eind > 0 => queue.push((sind, eind - 1)),
+            None => {}
+            Some(rnaid) => {
+                selected.push(rnaid);
+                inside.term(sind, rnaid, |x| queue.push(x));
+            }
+        }
+    }
+    selected.sort();
+    selected
+}
+
+pub fn sample<Idx, IR, Score>(ir: &[IR], scores: &[Score], temperature: f64, n: usize, seed: u64) -> Vec<Vec<usize>>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    if ir.is_empty() {
+        return vec![vec![]; n];
+    }
+
+    let weights: Vec<_> = scores.iter().map(|x| x.to_f64() / temperature).collect();
+    let inside = Inside::new(index::Index::new(ir), &weights);
+
+    let mut rng = Rng::new(seed);
+    (0..n).map(|_| traceback(&inside, &mut rng)).collect()
+}
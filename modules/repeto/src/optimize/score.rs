@@ -0,0 +1,146 @@
+use std::fmt::Debug;
+
+/// Numeric type that can be used to score repeats during the optimization.
+///
+/// Integer scores are compared exactly. Floating point scores are considered tied when they differ by less
+/// than a small relative tolerance, so rounding errors in sums don't change the optimal solution.
+/// NaN values are not valid scores. Tuples of scores (up to 4 objectives) are compared lexicographically, e.g.
+/// (data support, alignment score) uses the alignment score only to break ties in the data support.
+pub trait Score: Copy + PartialOrd + Debug {
+    fn zero() -> Self;
+
+    fn add(self, other: Self) -> Self;
+
+    fn sub(self, other: Self) -> Self;
+
+    /// Is the score strictly greater than the other one, beyond the tie tolerance?
+    fn exceeds(self, other: Self) -> bool;
+
+    /// Fraction numerator / denominator of the score, rounded towards zero for integers.
+    fn scale(self, numerator: usize, denominator: usize) -> Self;
+
+    fn to_f64(self) -> f64;
+
+    fn is_valid(self) -> bool { true }
+}
+
+macro_rules! integer {
+    ($($t:ty)*) => ($(
+        impl Score for $t {
+            fn zero() -> Self { 0 }
+
+            fn add(self, other: Self) -> Self { self + other }
+
+            fn sub(self, other: Self) -> Self { self - other }
+
+            fn exceeds(self, other: Self) -> bool { self > other }
+
+            fn scale(self, numerator: usize, denominator: usize) -> Self {
+                (self as i128 * numerator as i128 / denominator as i128) as Self
+            }
+
+            fn to_f64(self) -> f64 { self as f64 }
+        }
+    )*)
+}
+
+integer! { i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize }
+
+macro_rules! float {
+    ($($t:ty, $tolerance:expr);*) => ($(
+        impl Score for $t {
+            fn zero() -> Self { 0.0 }
+
+            fn add(self, other: Self) -> Self { self + other }
+
+            fn sub(self, other: Self) -> Self { self - other }
+
+            fn exceeds(self, other: Self) -> bool {
+                self - other > $tolerance * self.abs().max(other.abs())
+            }
+
+            fn scale(self, numerator: usize, denominator: usize) -> Self {
+                self * numerator as Self / denominator as Self
+            }
+
+            fn to_f64(self) -> f64 { self as f64 }
+
+            fn is_valid(self) -> bool { !self.is_nan() }
+        }
+    )*)
+}
+
+float! { f32, 1e-5; f64, 1e-9 }
+
+// Tuples are compared lexicographically: the next objective is used only when previous ones are tied
+macro_rules! lexicographic {
+    ($(($($t:ident $i:tt),+));*) => ($(
+        impl<$($t: Score),+> Score for ($($t,)+) {
+            fn zero() -> Self { ($($t::zero(),)+) }
+
+            fn add(self, other: Self) -> Self { ($(self.$i.add(other.$i),)+) }
+
+            fn sub(self, other: Self) -> Self { ($(self.$i.sub(other.$i),)+) }
+
+            fn exceeds(self, other: Self) -> bool {
+                $(
+                    if self.$i.exceeds(other.$i) {
+                        return true;
+                    } else if other.$i.exceeds(self.$i) {
+                        return false;
+                    }
+                )+
+                false
+            }
+
+            fn scale(self, numerator: usize, denominator: usize) -> Self {
+                ($(self.$i.scale(numerator, denominator),)+)
+            }
+
+            // Only the primary objective is meaningful as a single number
+            fn to_f64(self) -> f64 { self.0.to_f64() }
+
+            fn is_valid(self) -> bool { true $(&& self.$i.is_valid())+ }
+        }
+    )*)
+}
+
+lexicographic! { (A 0, B 1); (A 0, B 1, C 2); (A 0, B 1, C 2, D 3) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties() {
+        assert!(2.exceeds(1) && !1.exceeds(1) && !1.exceeds(2));
+
+        assert!(!(0.1 + 0.2).exceeds(0.3) && !0.3.exceeds(0.1 + 0.2));
+        assert!(0.31.exceeds(0.3));
+        assert!(!1e12.exceeds(1e12 - 1e-3) && 1e12.exceeds(1e12 - 1e4));
+        assert!(!(0.1f32 + 0.2f32).exceeds(0.3f32));
+
+        // The tolerance is relative, tiny scores are still positive
+        assert!(1e-7.exceeds(0.0) && 1e-300.exceeds(0.0) && 1e-6f32.exceeds(0.0));
+        assert!(!(1e-7 + 1e-17).exceeds(1e-7) && 1.1e-7.exceeds(1e-7));
+
+        assert_eq!(7.scale(2, 3), 4);
+        assert_eq!((-7).scale(2, 3), -4);
+        assert_eq!(3.0.scale(1, 4), 0.75);
+
+        assert!(!f64::NAN.is_valid() && 1.0.is_valid() && 1.is_valid());
+    }
+
+    #[test]
+    fn lexicographic() {
+        assert!((2, 0.0).exceeds((1, 5.0)) && (1, 5.0).exceeds((1, 4.0)));
+        assert!(!(1, 0.1 + 0.2).exceeds((1, 0.3)) && !(1, 0.3).exceeds((1, 0.1 + 0.2)));
+        assert!((0.1 + 0.2, 2).exceeds((0.3, 1)));
+        assert!((0, 0, 1).exceeds(<(i32, i32, i32)>::zero()) && !(0, -1, 5).exceeds((0, 0, 0)));
+
+        assert_eq!((1, 2.5).add((2, 0.5)), (3, 3.0));
+        assert_eq!((7, -7).scale(2, 3), (4, -4));
+        assert_eq!((3.0, 1).to_f64(), 3.0);
+        assert!(!(1, f64::NAN).is_valid() && (1, 1.0, 2).is_valid());
+    }
+}
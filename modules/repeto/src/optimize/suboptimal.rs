@@ -0,0 +1,126 @@
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use super::conflict::conflict;
+use super::inv;
+use super::score;
+
+// Lawler's k-best enumeration: each solution defines a subspace (forced in / forced out repeats), which is split
+// into disjoint subspaces after the solution is reported. For the i-th free repeat in the solution, the child
+// subspace forces in all previous free repeats and forces out the i-th one.
+//
+// Non-positive repeats are never considered: for any coherent set they only decrease the total score. Hence, an
+// optimal solution can't have coherent positive-score supersets, and branching on its repeats covers the whole
+// subspace.
+struct Node<Score: score::Score> {
+    score: Score,
+    // Insertion order to report ties in a deterministic fashion
+    order: Reverse<usize>,
+    selected: Vec<usize>,
+    include: Vec<usize>,
+    exclude: Vec<usize>,
+}
+
+impl<Score: score::Score> PartialEq for Node<Score> {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl<Score: score::Score> Eq for Node<Score> {}
+
+impl<Score: score::Score> PartialOrd for Node<Score> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<Score: score::Score> Ord for Node<Score> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Scores are never NaN
+        self.score.partial_cmp(&other.score).unwrap().then(self.order.cmp(&other.order))
+    }
+}
+
+pub fn enumerate<Idx, IR, Score>(
+    ir: &[IR], scores: &[Score], mut proceed: impl FnMut(usize, Score) -> bool,
+) -> Vec<(Vec<usize>, Score)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    let mut results = Vec::new();
+    let mut heap = BinaryHeap::new();
+
+    let mut order = 0;
+    if let Some((selected, score)) = solve(ir, scores, &[], &[]) {
+        heap.push(Node { score, order: Reverse(order), selected, include: vec![], exclude: vec![] });
+        order += 1;
+    }
+
+    while let Some(node) = heap.pop() {
+        if !proceed(results.len(), node.score) {
+            break;
+        }
+
+        let mut free: Vec<_> = node.selected.iter()
+            .filter(|x| !node.include.contains(x))
+            .copied()
+            .collect();
+        free.sort();
+
+        for (i, &rnaid) in free.iter().enumerate() {
+            let mut include = node.include.clone();
+            include.extend_from_slice(&free[..i]);
+            let mut exclude = node.exclude.clone();
+            exclude.push(rnaid);
+
+            if let Some((selected, score)) = solve(ir, scores, &include, &exclude) {
+                heap.push(Node { score, order: Reverse(order), selected, include, exclude });
+                order += 1;
+            }
+        }
+        results.push((node.selected, node.score));
+    }
+    results
+}
+
+// Best solution that contains all included repeats and none of the excluded ones.
+// Returns None if included repeats are not coherent.
+pub fn solve<Idx, IR, Score>(
+    ir: &[IR], scores: &[Score], include: &[usize], exclude: &[usize],
+) -> Option<(Vec<usize>, Score)>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>,
+        Score: score::Score
+{
+    for (i, &a) in include.iter().enumerate() {
+        for &b in &include[i + 1..] {
+            if a == b || conflict(ir[a].borrow(), ir[b].borrow()).is_some() {
+                return None;
+            }
+        }
+    }
+
+    let mut skip = vec![false; ir.len()];
+    for &x in include.iter().chain(exclude) {
+        skip[x] = true;
+    }
+
+    let candidates: Vec<_> = (0..ir.len())
+        .filter(|&x| {
+            !skip[x] && scores[x].exceeds(Score::zero()) &&
+                include.iter().all(|&y| conflict(ir[x].borrow(), ir[y].borrow()).is_none())
+        })
+        .collect();
+
+    let subset: Vec<_> = candidates.iter().map(|&x| ir[x].borrow()).collect();
+    let subscores: Vec<_> = candidates.iter().map(|&x| scores[x]).collect();
+    let (solution, mut score) = super::run(&subset, &subscores);
+
+    let mut selected = include.to_vec();
+    for &x in include {
+        score = score.add(scores[x]);
+    }
+    selected.extend(solution.into_iter().map(|x| candidates[x]));
+    Some((selected, score))
+}
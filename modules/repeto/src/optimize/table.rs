@@ -0,0 +1,52 @@
+use std::ops::Range;
+
+// Banded DP table stored in a single flat buffer. Each row (start anchor) keeps a contiguous run of ends,
+// from the first end located after the start to the last end required by the solution.
+pub struct Table<T: Copy> {
+    // Row -> (first stored end, cells in the buffer)
+    rows: Vec<(usize, Range<usize>)>,
+    cells: Vec<T>,
+    // Value of cells outside of the stored bands
+    empty: T,
+}
+
+impl<T: Copy> Table<T> {
+    pub fn new(empty: T) -> Self {
+        Self { rows: vec![], cells: vec![], empty }
+    }
+
+    // Allocate rows with the given (first, last) ends, rows are not stored if None
+    pub fn reset(&mut self, extents: impl Iterator<Item=Option<(usize, usize)>>) {
+        self.rows.clear();
+        let mut total = 0;
+        for extent in extents {
+            match extent {
+                Some((first, last)) if first <= last => {
+                    self.rows.push((first, total..total + last - first + 1));
+                    total += last - first + 1;
+                }
+                _ => self.rows.push((0, total..total)),
+            }
+        }
+
+        self.cells.clear();
+        self.cells.resize(total, self.empty);
+    }
+
+    // Cells located before the row start are empty, cells after the last stored end are never requested
+    pub fn get(&self, sind: usize, eind: usize) -> T {
+        let (first, range) = &self.rows[sind];
+        if eind < *first || range.is_empty() {
+            return self.empty;
+        }
+        // Otherwise the cell would be silently read from the next row
+        assert!(range.start + eind - first < range.end, "End {eind} is outside of the stored band of row {sind}");
+        self.cells[range.start + eind - first]
+    }
+
+    pub fn set(&mut self, sind: usize, eind: usize, cell: T) {
+        let (first, range) = &self.rows[sind];
+        debug_assert!(*first <= eind && range.start + eind - first < range.end);
+        self.cells[range.start + eind - first] = cell;
+    }
+}
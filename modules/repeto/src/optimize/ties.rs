@@ -0,0 +1,37 @@
+use itertools::Itertools;
+
+use super::inv;
+
+/// Policy to choose among coherent sets of repeats with equal total scores.
+///
+/// Remaining ties are broken by the smallest sum of repeat start positions, then by the order of repeat coordinates,
+/// and only then by the input order (i.e. only for identical repeats).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TieBreaking {
+    /// Prefer solutions with fewer repeats
+    Fewest,
+    /// Prefer solutions with more repeats
+    Most,
+}
+
+// Canonical order of repeats: the DP visits tied candidates in the same order for any input permutation
+pub fn canonical<Idx: inv::Coordinate>(ir: &[&inv::Repeat<Idx>]) -> Vec<usize> {
+    (0..ir.len())
+        .sorted_by_key(|x| ir[*x].seqranges().map(|r| (r.start, r.end)).collect_vec())
+        .collect()
+}
+
+// Secondary objectives of each repeat, they never outweigh the score itself
+pub fn objectives<Idx: inv::Coordinate>(ir: &[&inv::Repeat<Idx>], policy: TieBreaking) -> Vec<(i64, i64)> {
+    let origin = match ir.iter().map(|x| x.brange().start).min() {
+        Some(x) => x,
+        None => return vec![],
+    };
+    let count = match policy {
+        TieBreaking::Fewest => -1,
+        TieBreaking::Most => 1,
+    };
+    ir.iter()
+        .map(|x| (count, -(x.brange().start - origin).to_i64().unwrap()))
+        .collect()
+}
@@ -0,0 +1,193 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use super::conflict::{conflict, Conflict};
+use super::{inv, score};
+
+// Remove `outer` base pairs from the outer end of the repeat and `inner` base pairs from its inner end. Whole segments
+// are never removed, i.e. the trimmed repeat keeps at least one base pair in its first and last segments.
+fn trim<Idx>(repeat: &inv::Repeat<Idx>, outer: Idx, inner: Idx) -> Option<inv::Repeat<Idx>>
+    where Idx: inv::Coordinate + Debug
+{
+    let segments = repeat.segments();
+    let length = |x: &inv::Segment<Idx>| x.left().end - x.left().start;
+
+    let (first, last) = (&segments[0], segments.last().unwrap());
+    let fits = if segments.len() == 1 {
+        outer + inner < length(first)
+    } else {
+        outer < length(first) && inner < length(last)
+    };
+    if !fits {
+        return None;
+    }
+
+    let mut segments = segments.clone();
+    let s = &segments[0];
+    segments[0] = inv::Segment::new(s.left().start + outer..s.left().end, s.right().start..s.right().end - outer);
+    let s = segments.last().unwrap();
+    *segments.last_mut().unwrap() = inv::Segment::new(
+        s.left().start..s.left().end - inner, s.right().start + inner..s.right().end,
+    );
+    Some(inv::Repeat::new(segments))
+}
+
+// Minimal (outer, inner) trims of the repeat that free all nucleotides covered by the blocks, ordered by the total
+// number of removed base pairs. Empty if some of them are outside the arm ends, e.g. in the middle segments, or the
+// repeat can't be trimmed that much.
+fn clearances<'a, Idx>(
+    repeat: &inv::Repeat<Idx>, blocks: impl Iterator<Item=&'a Range<Idx>>,
+) -> Vec<(Idx, Idx)>
+    where Idx: inv::Coordinate + Debug + 'a
+{
+    let segments = repeat.segments();
+    let last = segments.len() - 1;
+
+    // Each shared block is freed by a large enough outer trim, a large enough inner trim, or both
+    let mut required = Vec::new();
+    for block in blocks {
+        for (ind, segment) in segments.iter().enumerate() {
+            for (arm, isleft) in [(segment.left(), true), (segment.right(), false)] {
+                let (start, end) = (arm.start.max(block.start), arm.end.min(block.end));
+                if start >= end {
+                    continue;
+                }
+                let outer = (ind == 0).then(|| if isleft { end - arm.start } else { arm.end - start });
+                let inner = (ind == last).then(|| if isleft { arm.end - start } else { end - arm.start });
+                if outer.is_none() && inner.is_none() {
+                    return vec![];
+                }
+                required.push((outer, inner));
+            }
+        }
+    }
+
+    // Minimal outer trims are either zero or free one of the blocks on their own
+    std::iter::once(Idx::zero())
+        .chain(required.iter().filter_map(|x| x.0))
+        .sorted()
+        .dedup()
+        .filter_map(|outer| {
+            let mut inner = Idx::zero();
+            for (o, i) in &required {
+                if o.map_or(true, |x| x > outer) {
+                    inner = inner.max((*i)?);
+                }
+            }
+            trim(repeat, outer, inner).map(|_| (outer, inner))
+        })
+        .sorted_by_key(|(outer, inner)| (*outer + *inner, *outer))
+        .collect()
+}
+
+// Pairs of repeats sharing at least one nucleotide, each pair is reported once as (smaller index, larger index)
+fn overlaps<Idx: inv::Coordinate>(ir: &[&inv::Repeat<Idx>]) -> Vec<(usize, usize)> {
+    let blocks = ir.iter()
+        .enumerate()
+        .flat_map(|(ind, x)| x.seqranges().map(move |block| (block, ind)))
+        .sorted_by_key(|x| x.0.start)
+        .collect_vec();
+
+    let mut pairs = Vec::new();
+    for (i, (block, a)) in blocks.iter().enumerate() {
+        for (_, b) in blocks[i + 1..].iter().take_while(|x| x.0.start < block.end) {
+            if a != b {
+                pairs.push((*a.min(b), *a.max(b)));
+            }
+        }
+    }
+    pairs.sort();
+    pairs.dedup();
+    pairs
+}
+
+pub fn run<Idx, Score, Total>(
+    ir: &[&inv::Repeat<Idx>],
+    scores: &[Score],
+    tolerance: Idx,
+    solve: impl FnOnce(&[&inv::Repeat<Idx>], &[usize], &[Score]) -> (Vec<usize>, Total),
+) -> (Vec<(usize, inv::Repeat<Idx>)>, Total)
+    where
+        Idx: inv::Coordinate + Debug,
+        Score: score::Score
+{
+    // Higher-scoring repeats come first, ties are broken by the index
+    let rank = |a: &usize, b: &usize| {
+        scores[*b].partial_cmp(&scores[*a]).unwrap_or(Ordering::Equal).then(a.cmp(b))
+    };
+
+    // Each repeat gives up its arm ends to all higher-scoring repeats it overlaps by at most `tolerance` nucleotides.
+    // Such pairs of cores share no nucleotides, i.e. the DP can select them together.
+    let pairs = overlaps(ir).into_iter()
+        .map(|(a, b)| if rank(&a, &b).is_lt() { (a, b) } else { (b, a) })
+        .collect_vec();
+    let mut trims = vec![(Idx::zero(), Idx::zero()); ir.len()];
+    for &(higher, lower) in &pairs {
+        let coherent = |(outer, inner): &(Idx, Idx)| {
+            *outer + *inner <= tolerance && conflict(&trim(ir[lower], *outer, *inner).unwrap(), ir[higher]).is_none()
+        };
+        if let Some((outer, inner)) = clearances(ir[lower], ir[higher].seqranges()).into_iter().find(coherent) {
+            let trim = &mut trims[lower];
+            *trim = (trim.0.max(outer), trim.1.max(inner));
+        }
+    }
+    let mut cores = Vec::with_capacity(ir.len());
+    for (repeat, (outer, inner)) in ir.iter().zip(trims.iter_mut()) {
+        match trim(repeat, *outer, *inner) {
+            Some(core) => cores.push(core),
+            None => {
+                cores.push((*repeat).clone());
+                (*outer, *inner) = (Idx::zero(), Idx::zero());
+            }
+        }
+    }
+
+    // Overlapping repeats can be selected together only if the lower-scoring core is free of the higher-scoring
+    // repeat. Otherwise, e.g. if the overlap exceeds the tolerance but trimming for other repeats removed it from both
+    // cores, full repeats are restored. Restored repeats can break other pairs => repeat until nothing changes.
+    let mut bypair = vec![Vec::new(); ir.len()];
+    for (ind, (higher, lower)) in pairs.iter().enumerate() {
+        bypair[*higher].push(ind);
+        bypair[*lower].push(ind);
+    }
+    let mut pending = (0..pairs.len()).collect_vec();
+    while let Some(ind) = pending.pop() {
+        let (higher, lower) = pairs[ind];
+        if conflict(&cores[higher], &cores[lower]).is_some()
+            || conflict(&cores[lower], ir[higher]) != Some(Conflict::Overlap) {
+            continue;
+        }
+        for x in [higher, lower] {
+            if &cores[x] != ir[x] {
+                cores[x] = ir[x].clone();
+                trims[x] = (Idx::zero(), Idx::zero());
+                pending.extend(&bypair[x]);
+            }
+        }
+    }
+    let (selected, total) = solve(&cores.iter().collect_vec(), &(0..ir.len()).collect_vec(), scores);
+
+    // Cores are trimmed against all higher-scoring repeats, including those that weren't selected. Selected repeats
+    // regrow their arm ends in the descending score order as long as they don't overlap other selected repeats. Arms
+    // are only extended, i.e. all repeats remain coherent.
+    let mut chosen = selected.into_iter().sorted_by(rank).map(|x| (x, cores[x].clone())).collect_vec();
+    let mut neighbours = vec![Vec::new(); chosen.len()];
+    for (a, b) in overlaps(&chosen.iter().map(|x| ir[x.0]).collect_vec()) {
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+    for i in 0..chosen.len() {
+        let repeat = ir[chosen[i].0];
+        let blocks = neighbours[i].iter().flat_map(|x| chosen[*x].1.seqranges()).collect_vec();
+        let (outer, inner) = clearances(repeat, blocks.into_iter())
+            .into_iter()
+            .find(|(outer, inner)| *outer <= trims[chosen[i].0].0 && *inner <= trims[chosen[i].0].1)
+            .expect("Selected cores must not overlap each other");
+        chosen[i].1 = trim(repeat, outer, inner).unwrap();
+    }
+    chosen.sort_by_key(|x| x.0);
+    (chosen, total)
+}
@@ -0,0 +1,81 @@
+use std::borrow::Borrow;
+
+use derive_getters::{Dissolve, Getters};
+
+use super::inv;
+
+/// Selected repeat together with repeats nested in its gaps.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Getters, Dissolve)]
+pub struct TreeNode {
+    rnaid: usize,
+    // Gap of the parent repeat that contains this one, counted from left to right between parent's blocks.
+    // None for top-level repeats.
+    gap: Option<usize>,
+    // Nested repeats ordered by their start position
+    children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    pub fn new(rnaid: usize, gap: Option<usize>, children: Vec<TreeNode>) -> Self {
+        Self { rnaid, gap, children }
+    }
+}
+
+pub fn nest<Idx, IR>(ir: &[IR], selected: &[usize]) -> Vec<TreeNode>
+    where
+        Idx: inv::Coordinate,
+        IR: Borrow<inv::Repeat<Idx>>
+{
+    let mut order = selected.to_vec();
+    order.sort_by_key(|x| {
+        let brange = ir[*x].borrow().brange();
+        (brange.start, std::cmp::Reverse(brange.end))
+    });
+
+    // Nodes are appended in the pre-order => children always follow their parents
+    let mut nodes: Vec<(Option<usize>, TreeNode)> = Vec::with_capacity(order.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for rnaid in order {
+        let brange = ir[rnaid].borrow().brange();
+        while let Some(&top) = stack.last() {
+            if ir[nodes[top].1.rnaid].borrow().brange().end <= brange.start {
+                stack.pop();
+            } else {
+                break;
+            }
+        }
+
+        let (parent, gap) = match stack.last() {
+            None => (None, None),
+            Some(&parent) => {
+                let blocks: Vec<_> = ir[nodes[parent].1.rnaid].borrow().seqranges().collect();
+                let gap = blocks.partition_point(|x| x.end <= brange.start);
+                assert!(
+                    0 < gap && gap < blocks.len() && brange.end <= blocks[gap].start,
+                    "Selected repeats must be coherent"
+                );
+                (Some(parent), Some(gap - 1))
+            }
+        };
+
+        stack.push(nodes.len());
+        nodes.push((parent, TreeNode { rnaid, gap, children: vec![] }));
+    }
+
+    let mut roots = Vec::new();
+    while let Some((parent, node)) = nodes.pop() {
+        match parent {
+            None => roots.push(node),
+            Some(parent) => nodes[parent].1.children.push(node),
+        }
+    }
+
+    // Nodes were collected in the reverse order
+    roots.reverse();
+    let mut queue: Vec<&mut TreeNode> = roots.iter_mut().collect();
+    while let Some(node) = queue.pop() {
+        node.children.reverse();
+        queue.extend(node.children.iter_mut());
+    }
+    roots
+}
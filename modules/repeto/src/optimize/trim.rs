@@ -0,0 +1,155 @@
+use std::fmt::Debug;
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use super::conflict::conflict;
+use super::inv;
+use super::score;
+use super::weights::Weights;
+
+/// Level at which repeats can be trimmed during the optimization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Trimming {
+    /// Keep or drop whole segments
+    Segments,
+    /// Split segments at positions where blocks of other repeats start or end
+    Basepairs,
+}
+
+// Smallest piece of a repeat that can be included in the solution.
+// Atoms of a single repeat are nested, i.e. any subset of them is coherent.
+struct Atom<Idx: inv::Coordinate> {
+    rnaid: usize,
+    segment: usize,
+    repeat: inv::Repeat<Idx>,
+    // Base pairs of the source repeat retained before and after including the atom, from outer to inner
+    retained: Range<usize>,
+    total: usize,
+}
+
+impl<Idx: inv::Coordinate> Atom<Idx> {
+    // Share of the score proportional to the number of atom's base pairs
+    fn share<Score: score::Score>(&self, score: Score) -> Score {
+        score.scale(self.retained.end, self.total).sub(score.scale(self.retained.start, self.total))
+    }
+}
+
+// Each atom gets a share of its repeat's score proportional to the number of its base pairs. Per-nucleotide weights
+// are added for retained positions only, while their constant term is shared in the same way as the score.
+pub fn run<Idx, Score, Total>(
+    ir: &[&inv::Repeat<Idx>],
+    scores: &[Score],
+    weights: Option<&Weights<Idx, Score>>,
+    trimming: Trimming,
+    solve: impl FnOnce(&[&inv::Repeat<Idx>], &[usize], &[Score]) -> (Vec<usize>, Total),
+) -> (Vec<(usize, inv::Repeat<Idx>)>, Total)
+    where
+        Idx: inv::Coordinate + Debug,
+        Score: score::Score
+{
+    let atoms = atomize(ir, trimming);
+    let atscores = atoms.iter()
+        .map(|x| {
+            let score = x.share(scores[x.rnaid]);
+            match weights {
+                Some(w) => score.add(w.paired(&x.repeat)).add(x.share(*w.constant())),
+                None => score,
+            }
+        })
+        .collect_vec();
+
+    let repeats = atoms.iter().map(|x| &x.repeat).collect_vec();
+    let rnaids = atoms.iter().map(|x| x.rnaid).collect_vec();
+    let (selected, total) = solve(&repeats, &rnaids, &atscores);
+    let selected = selected.into_iter().sorted().map(|x| &atoms[x]).collect_vec();
+    (merge(&selected), total)
+}
+
+fn atomize<Idx>(ir: &[&inv::Repeat<Idx>], trimming: Trimming) -> Vec<Atom<Idx>>
+    where Idx: inv::Coordinate + Debug
+{
+    let boundaries = match trimming {
+        Trimming::Segments => vec![],
+        Trimming::Basepairs => ir.iter()
+            .flat_map(|x| x.seqranges().flat_map(|r| [r.start, r.end]).collect_vec())
+            .sorted()
+            .dedup()
+            .collect(),
+    };
+
+    let mut atoms = Vec::new();
+    for (rnaid, repeat) in ir.iter().enumerate() {
+        let total = pairs(&(repeat.seqlen() >> 1));
+
+        let mut retained = 0;
+        for (segment, s) in repeat.segments().iter().enumerate() {
+            for (start, end) in cuts(s, &boundaries).into_iter().tuple_windows() {
+                let (left, right) = (s.left(), s.right());
+                let atom = inv::Segment::new(
+                    (left.start + start)..(left.start + end), (right.end - end)..(right.end - start),
+                );
+
+                let before = retained;
+                retained += pairs(&(end - start));
+                atoms.push(Atom {
+                    rnaid, segment, repeat: inv::Repeat::new(vec![atom]), retained: before..retained, total,
+                });
+            }
+        }
+    }
+    atoms
+}
+
+fn pairs<Idx: inv::Coordinate>(length: &Idx) -> usize { length.to_usize().unwrap() }
+
+// Offsets of all cut points within the segment, including its start and end
+fn cuts<Idx: inv::Coordinate>(segment: &inv::Segment<Idx>, boundaries: &[Idx]) -> Vec<Idx> {
+    let (left, right) = (segment.left(), segment.right());
+    let inside = |range: &Range<Idx>| {
+        let start = boundaries.partition_point(|x| *x <= range.start);
+        let end = boundaries.partition_point(|x| *x < range.end);
+        &boundaries[start..end]
+    };
+
+    // Right arm is paired in the reverse direction
+    let mut offsets = vec![Idx::zero(), left.end - left.start];
+    offsets.extend(inside(left).iter().map(|x| *x - left.start));
+    offsets.extend(inside(right).iter().map(|x| right.end - *x));
+    offsets.sort();
+    offsets.dedup();
+    offsets
+}
+
+// Merge selected atoms of each repeat from outer to inner. Atoms are split into several pieces when merging them
+// would make the piece conflict with atoms of other repeats.
+fn merge<Idx>(atoms: &[&Atom<Idx>]) -> Vec<(usize, inv::Repeat<Idx>)>
+    where Idx: inv::Coordinate + Debug
+{
+    let mut result = Vec::new();
+    for (rnaid, group) in &atoms.iter().group_by(|x| x.rnaid) {
+        let others = atoms.iter().filter(|x| x.rnaid != rnaid).collect_vec();
+
+        let mut piece: Vec<(usize, inv::Segment<Idx>)> = Vec::new();
+        for atom in group {
+            let next = atom.repeat.segments()[0].clone();
+            let mut candidate = piece.clone();
+            match candidate.last_mut() {
+                Some((segment, last)) if *segment == atom.segment && last.left().end == next.left().start => {
+                    *last = inv::Segment::new(last.left().start..next.left().end, next.right().start..last.right().end);
+                }
+                _ => candidate.push((atom.segment, next.clone())),
+            }
+
+            let repeat = inv::Repeat::new(candidate.iter().map(|x| x.1.clone()).collect());
+            if others.iter().all(|x| conflict(&repeat, &x.repeat).is_none()) {
+                piece = candidate;
+            } else {
+                result.push((rnaid, inv::Repeat::new(piece.into_iter().map(|x| x.1).collect())));
+                piece = vec![(atom.segment, next)];
+            }
+        }
+        result.push((rnaid, inv::Repeat::new(piece.into_iter().map(|x| x.1).collect())));
+    }
+    result
+}
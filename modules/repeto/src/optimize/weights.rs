@@ -0,0 +1,50 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+
+use derive_getters::{Dissolve, Getters};
+
+use super::{inv, score};
+
+/// Per-nucleotide scoring model: a repeat is scored as the sum of weights at all its paired positions plus a
+/// constant per-repeat term.
+#[derive(Clone, Debug, PartialEq, Getters, Dissolve)]
+pub struct Weights<Idx: inv::Coordinate, Score: score::Score> {
+    // Weight of each position starting from the offset
+    track: Vec<Score>,
+    // Sequence position of the first weight
+    offset: Idx,
+    // Score added to each repeat
+    constant: Score,
+}
+
+impl<Idx: inv::Coordinate, Score: score::Score> Weights<Idx, Score> {
+    /// Positions outside of the track have zero weight.
+    pub fn new(track: Vec<Score>, offset: Idx, constant: Score) -> Self {
+        assert!(track.iter().all(|x| x.is_valid()) && constant.is_valid(), "Weights must not be NaN");
+        Self { track, offset, constant }
+    }
+
+    /// Score of the repeat under the model.
+    pub fn score(&self, repeat: &inv::Repeat<Idx>) -> Score {
+        self.constant.add(self.paired(repeat))
+    }
+
+    /// Scores of all repeats under the model.
+    pub fn scores<IR: Borrow<inv::Repeat<Idx>>>(&self, ir: &[IR]) -> Vec<Score> {
+        ir.iter().map(|x| self.score(x.borrow())).collect()
+    }
+
+    // Total weight of paired positions without the constant term
+    pub(super) fn paired(&self, repeat: &inv::Repeat<Idx>) -> Score {
+        repeat.seqranges().fold(Score::zero(), |total, range| total.add(self.sum(range)))
+    }
+
+    fn sum(&self, range: &Range<Idx>) -> Score {
+        let index = |x: Idx| if x <= self.offset {
+            0
+        } else {
+            (x - self.offset).to_usize().unwrap().min(self.track.len())
+        };
+        self.track[index(range.start)..index(range.end)].iter().fold(Score::zero(), |total, x| total.add(*x))
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+use derive_getters::{Dissolve, Getters};
+
+use super::Coordinate;
+use super::inv;
+use crate::io;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+    #[default]
+    Unknown,
+}
+
+impl Display for Strand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Strand::Forward => "+",
+            Strand::Reverse => "-",
+            Strand::Unknown => ".",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+impl TryFrom<&str> for Strand {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "+" => Ok(Strand::Forward),
+            "-" => Ok(Strand::Reverse),
+            "." => Ok(Strand::Unknown),
+            _ => Err(format!("Unknown strand: {value}")),
+        }
+    }
+}
+
+/// Inverted repeat located on a given contig & strand. Coordinates are always in the forward genome orientation.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Getters, Dissolve)]
+pub struct GenomicRepeat<Idx: Coordinate> {
+    contig: String,
+    strand: Strand,
+    repeat: inv::Repeat<Idx>,
+}
+
+impl<Idx: Coordinate> GenomicRepeat<Idx> {
+    pub fn new(contig: String, strand: Strand, repeat: inv::Repeat<Idx>) -> Self {
+        Self { contig, strand, repeat }
+    }
+
+    /// Map a repeat predicted in a genomic window to the genome coordinates, see `inv::Repeat::to_genome`.
+    ///
+    /// Window sequence for the reverse strand is expected to be reverse complemented (e.g. `bedtools getfasta -s`).
+    pub fn from_window(contig: String, strand: Strand, window: Range<Idx>, mut repeat: inv::Repeat<Idx>) -> Self {
+        repeat.to_genome(&window, strand);
+        Self { contig, strand, repeat }
+    }
+
+    pub fn to_bed12(&self, name: &str, score: u16, color: &str) -> String
+        where Idx: Display
+    {
+        let blocks: Vec<_> = self.repeat.seqranges().cloned().collect();
+        io::bed12(&self.contig, &self.strand.to_string(), &blocks, name, score, color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat() -> inv::Repeat<isize> {
+        inv::Repeat::new(vec![
+            inv::Segment::new(0..3, 10..13),
+            inv::Segment::new(4..5, 8..9),
+        ])
+    }
+
+    #[test]
+    fn from_window() {
+        let forward = GenomicRepeat::from_window("1".to_string(), Strand::Forward, 100..120, repeat());
+        assert_eq!(forward.repeat().brange(), 100..113);
+        assert_eq!(forward.to_bed12(".", 0, "0,0,0"), "1\t100\t113\t.\t0\t+\t100\t113\t0,0,0\t4\t3,1,1,3\t0,4,8,10");
+
+        // Window [100, 120) on the reverse strand: window position 0 is the genome position 119
+        let reverse = GenomicRepeat::from_window("1".to_string(), Strand::Reverse, 100..120, repeat());
+        let expected = inv::Repeat::new(vec![
+            inv::Segment::new(107..110, 117..120),
+            inv::Segment::new(111..112, 115..116),
+        ]);
+        assert_eq!(reverse.repeat(), &expected);
+        assert_eq!(reverse.to_bed12("IR", 10, "255,0,0"), "1\t107\t120\tIR\t10\t-\t107\t120\t255,0,0\t4\t3,1,1,3\t0,4,8,10");
+    }
+
+    #[test]
+    fn strand() {
+        for symbol in ["+", "-", "."] {
+            assert_eq!(Strand::try_from(symbol).unwrap().to_string(), symbol);
+        }
+        assert!(Strand::try_from("*").is_err());
+    }
+}
@@ -5,6 +5,8 @@ use derive_getters::{Dissolve, Getters};
 use itertools::{chain, Itertools};
 
 pub use super::Coordinate;
+use super::genomic::Strand;
+use super::pairing::{self, PairingRules};
 
 #[derive(Eq, PartialEq, Hash, Clone, Getters, Dissolve)]
 pub struct Segment<Idx: Coordinate> {
@@ -36,6 +38,14 @@ impl<Idx: Coordinate> Segment<Idx> {
 
     fn brange(&self) -> Range<Idx> { self.left().start..self.right().end }
 
+    fn basepairs(&self) -> impl Iterator<Item=(Idx, Idx)> + '_ {
+        let length = (self.left.end - self.left.start).to_usize().unwrap();
+        (0..length).map(move |offset| {
+            let offset = Idx::from(offset).unwrap();
+            (self.left.start + offset, self.right.end - Idx::one() - offset)
+        })
+    }
+
     fn shift(&mut self, shift: &Idx) {
         self.left.start += *shift;
         self.left.end += *shift;
@@ -43,6 +53,11 @@ impl<Idx: Coordinate> Segment<Idx> {
         self.right.start += *shift;
         self.right.end += *shift;
     }
+
+    fn reflect(&mut self, length: &Idx) {
+        let mirror = |x: &Range<Idx>| (*length - x.end)..(*length - x.start);
+        (self.left, self.right) = (mirror(&self.right), mirror(&self.left));
+    }
 }
 
 
@@ -114,6 +129,32 @@ impl<Idx: Coordinate> Repeat<Idx> {
         for x in &mut self.segments { x.shift(shift) }
     }
 
+    /// Mirror coordinates inside a sequence of the given length, i.e. position p becomes length - 1 - p.
+    ///
+    /// Left and right arms of each segment are swapped, so segments stay ordered from outer to inner.
+    pub fn reflect(&mut self, length: &Idx) {
+        for x in &mut self.segments { x.reflect(length) }
+        debug_assert!(self.segments.iter().tuple_windows().all(|(prev, nxt)| {
+            prev.left.end <= nxt.left.start && prev.right.start >= nxt.right.end
+        }));
+    }
+
+    /// Map the repeat from window-relative coordinates to the genome.
+    ///
+    /// Windows on the reverse strand are expected to be reverse complemented, i.e. window position 0 is the last
+    /// window nucleotide on the forward strand.
+    pub fn to_genome(&mut self, window: &Range<Idx>, strand: Strand) {
+        if strand == Strand::Reverse {
+            self.reflect(&(window.end - window.start));
+        }
+        self.shift(&window.start);
+    }
+
+    /// Paired positions (left, right) of all segments, from outer to inner.
+    pub fn basepairs(&self) -> impl Iterator<Item=(Idx, Idx)> + '_ {
+        self.segments().iter().flat_map(|x| x.basepairs())
+    }
+
     pub fn seqranges(&self) -> impl Iterator<Item=&'_ Range<Idx>>
     {
         chain(
@@ -121,6 +162,55 @@ impl<Idx: Coordinate> Repeat<Idx> {
             self.segments().iter().rev().map(|x| x.right()),
         )
     }
+
+    /// Check that the repeat is complementary on the given sequence.
+    ///
+    /// Repeat coordinates must be relative to the sequence start, i.e. shifted back from genomic coordinates
+    /// if needed. Panics if the repeat doesn't fit into the sequence.
+    pub fn validate(&self, seq: &[u8], rules: PairingRules) -> Validation<Idx>
+        where Idx: Debug
+    {
+        let brange = self.brange();
+        assert!(
+            brange.start >= Idx::zero() && matches!(brange.end.to_usize(), Some(x) if x <= seq.len()),
+            "Repeat {:?} is outside of the sequence with length {}", self.brange(), seq.len()
+        );
+
+        let mut complementarity = Vec::with_capacity(self.segments.len());
+        let (mut noncanonical, mut mismatches) = (0, Vec::new());
+        for segment in &self.segments {
+            let mut paired = 0;
+            for (left, right) in segment.basepairs() {
+                let (a, b) = (seq[left.to_usize().unwrap()], seq[right.to_usize().unwrap()]);
+                if !rules.allows(a, b) {
+                    mismatches.push((left, right));
+                } else {
+                    paired += 1;
+                    if !pairing::is_canonical(a, b) {
+                        noncanonical += 1;
+                    }
+                }
+            }
+            let total = (segment.left.end - segment.left.start).to_usize().unwrap();
+            complementarity.push(paired as f64 / total as f64);
+        }
+
+        Validation { complementarity, noncanonical, mismatches }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Getters, Dissolve)]
+pub struct Validation<Idx: Coordinate> {
+    // Fraction of base pairs allowed by the pairing rules in each segment
+    complementarity: Vec<f64>,
+    // Allowed base pairs that are not Watson-Crick
+    noncanonical: usize,
+    // Positions (left, right) that can't form a base pair
+    mismatches: Vec<(Idx, Idx)>,
+}
+
+impl<Idx: Coordinate> Validation<Idx> {
+    pub fn is_valid(&self) -> bool { self.mismatches.is_empty() }
 }
 
 impl<Idx: Coordinate + Debug> Debug for Repeat<Idx> {
@@ -132,3 +222,63 @@ impl<Idx: Coordinate + Debug> Debug for Repeat<Idx> {
         )
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        // Positions 6 and 9 form a G-U wobble pair
+        let seq = b"AGCtaGGGuuCgct";
+        let repeat = Repeat::new(vec![
+            Segment::new(0..3, 11..14),
+            Segment::new(5..7, 9..11),
+        ]);
+
+        let result = repeat.validate(seq, PairingRules::WatsonCrick);
+        assert_eq!(result.complementarity(), &vec![1.0, 0.5]);
+        assert_eq!(*result.noncanonical(), 0);
+        assert_eq!(result.mismatches(), &vec![(6, 9)]);
+        assert!(!result.is_valid());
+
+        let result = repeat.validate(seq, PairingRules::Wobble);
+        assert_eq!(result.complementarity(), &vec![1.0, 1.0]);
+        assert_eq!(*result.noncanonical(), 1);
+        assert!(result.is_valid());
+    }
+
+    #[test]
+    fn reflect() {
+        let mut repeat = Repeat::new(vec![
+            Segment::new(0..3, 10..13),
+            Segment::new(4..5, 8..9),
+        ]);
+        repeat.reflect(&20);
+        assert_eq!(repeat, Repeat::new(vec![
+            Segment::new(7..10, 17..20),
+            Segment::new(11..12, 15..16),
+        ]));
+
+        repeat.reflect(&20);
+        let original = repeat.clone();
+        for strand in [Strand::Forward, Strand::Unknown] {
+            let mut shifted = original.clone();
+            shifted.to_genome(&(100..120), strand);
+            assert_eq!(shifted.brange(), 100..113);
+        }
+
+        repeat.to_genome(&(100..120), Strand::Reverse);
+        assert_eq!(repeat, Repeat::new(vec![
+            Segment::new(107..110, 117..120),
+            Segment::new(111..112, 115..116),
+        ]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_outside() {
+        Repeat::new(vec![Segment::new(0..3, 11..14)]).validate(b"ACGT", PairingRules::Wobble);
+    }
+}
@@ -1,6 +1,8 @@
 use num::traits::{NumAssign, PrimInt};
 
+pub mod genomic;
 pub mod inv;
+pub mod pairing;
 
 pub trait Coordinate: PrimInt + NumAssign {}
 
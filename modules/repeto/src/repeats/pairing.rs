@@ -0,0 +1,30 @@
+/// Rules that define which nucleotides can form a base pair.
+///
+/// Watson-Crick pairs (A-T, A-U, G-C) are always allowed. The wobble rules additionally permit G-U (G-T) pairs,
+/// which are common in RNA duplexes. Comparisons are case-insensitive, and ambiguous nucleotides never pair.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PairingRules {
+    #[default]
+    WatsonCrick,
+    Wobble,
+}
+
+impl PairingRules {
+    pub fn allows(&self, a: u8, b: u8) -> bool {
+        is_canonical(a, b) || (*self == PairingRules::Wobble && is_wobble(a, b))
+    }
+}
+
+pub fn is_canonical(a: u8, b: u8) -> bool {
+    matches!(
+        (a.to_ascii_uppercase(), b.to_ascii_uppercase()),
+        (b'A', b'T') | (b'T', b'A') | (b'A', b'U') | (b'U', b'A') | (b'G', b'C') | (b'C', b'G')
+    )
+}
+
+pub fn is_wobble(a: u8, b: u8) -> bool {
+    matches!(
+        (a.to_ascii_uppercase(), b.to_ascii_uppercase()),
+        (b'G', b'U') | (b'U', b'G') | (b'G', b'T') | (b'T', b'G')
+    )
+}
@@ -0,0 +1,63 @@
+use std::time::Instant;
+
+use repeto::optimize;
+use repeto::repeats::inv;
+
+// Synthetic inputs for the timings in docs/src/algo/matching.md, run them with:
+// cargo test --release -p repeto --test benchmark -- --ignored --nocapture
+
+// SplitMix64 => the same inputs on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, n: u64) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        (z ^ (z >> 31)) % n
+    }
+}
+
+fn report(name: &str, ir: &[inv::Repeat<i64>], scores: &[i64]) {
+    let start = Instant::now();
+    let (solution, score) = optimize::run(ir, scores);
+    println!("{name}: {:.2?}, {} repeats selected, total score {score}", start.elapsed(), solution.len());
+}
+
+#[test]
+#[ignore]
+fn random() {
+    let mut rng = Rng(42);
+    let (mut ir, mut scores) = (Vec::new(), Vec::new());
+    while ir.len() < 300_000 {
+        // 1-3 segments, each at least 1nt long
+        let mut left = rng.next(3_000_000) as i64;
+        let mut right = left + 20 + rng.next(2_000) as i64;
+        let mut segments = Vec::new();
+        for _ in 0..1 + rng.next(3) {
+            let length = 1 + rng.next(10) as i64;
+            if left + length > right - length {
+                break;
+            }
+            segments.push(inv::Segment::new(left..left + length, right - length..right));
+            left += length + rng.next(5) as i64;
+            right -= length + rng.next(5) as i64;
+        }
+        ir.push(inv::Repeat::new(segments));
+        scores.push(1 + rng.next(100) as i64);
+    }
+    report("300k random repeats", &ir, &scores);
+}
+
+#[test]
+#[ignore]
+fn nested() {
+    // Each repeat is nested in the inner gap of the previous one
+    let depth = 5_000;
+    let ir = (0..depth)
+        .map(|x| inv::Repeat::new(vec![inv::Segment::new(x..x + 1, 2 * depth - x - 1..2 * depth - x)]))
+        .collect::<Vec<_>>();
+    let scores = vec![1; depth as usize];
+    report("5k nested repeats", &ir, &scores);
+}